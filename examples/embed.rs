@@ -0,0 +1,64 @@
+// Runs Lox from a Rust program: `cargo run --example embed`
+
+use rlox::{from_lox, to_lox, HostClass, Lox, LoxObject};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+struct Counter {
+    count: f32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Config {
+    name: String,
+    port: u16,
+    tags: Vec<String>,
+    debug: Option<bool>,
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut lox = Lox::new();
+
+    lox.set_global("greeting", "Hello");
+    lox.register_fn("double", 1, |arguments| Ok(LoxObject::from(f32::try_from(&arguments[0])? * 2.0)));
+
+    lox.eval("var answer = double(21);")?;
+    lox.eval("fun greet(name) { return greeting + \", \" + name + \"!\"; }")?;
+
+    let greeting = String::try_from(lox.eval("greet(\"embedder\")")?)?;
+    let answer = f32::try_from(lox.get_global("answer").unwrap_or(LoxObject::Nil))?;
+
+    println!("{} The answer is {}.", greeting, answer);
+
+    // Capture what the script prints instead of letting it go to standard output
+    let output = Rc::new(RefCell::new(Vec::new()));
+    lox.set_output(output.clone());
+    lox.eval("for (var i = 1; i <= 3; i = i + 1) print i;")?;
+    println!("The script printed {:?}", String::from_utf8_lossy(&output.borrow()));
+
+    // A Rust struct as a Lox class, subclassed in Lox
+    let counter = HostClass::new("Counter", 1, |arguments| Ok(Counter { count: f32::try_from(&arguments[0])? }))
+        .method("increment", 0, |counter, _| {
+            counter.borrow_mut().count += 1.0;
+            Ok(LoxObject::Nil)
+        })
+        .getter("count", |counter| LoxObject::from(counter.borrow().count))
+        .setter("count", |counter, value| {
+            counter.borrow_mut().count = f32::try_from(value)?;
+            Ok(())
+        });
+    lox.register_class(counter);
+
+    lox.eval("class Twice < Counter { increment() { super.increment(); super.increment(); } }")?;
+    let count = lox.eval("var c = Twice(10); c.increment(); c.count = c.count + 100; c.count")?;
+    println!("The counter reached {}.", count);
+
+    // Hand a Rust struct to a script and read back what it made of it
+    let defaults = Config { name: "service".to_string(), port: 8080, tags: vec!["web".to_string()], debug: None };
+    lox.set_global("defaults", to_lox(&defaults)?);
+    let config: Config = from_lox(&lox.eval("defaults.port = defaults.port + 1; defaults.debug = true; defaults")?)?;
+    println!("The script configured {:?}", config);
+    Ok(())
+}
@@ -0,0 +1,624 @@
+use crate::expr::{
+    AssignExpr, BinaryExpr, CallExpr, Expr, ExpressionProcessor, GetExpr, GroupingExpr, LiteralExpr, LiteralValue,
+    LogicalExpr, SetExpr, SuperExpr, ThisExpr, UnaryExpr, VariableExpr,
+};
+use crate::scanner::TokenKind::*;
+use crate::scanner::{Position, Scanner, Span, Token, TokenKind};
+use crate::stmt::{
+    BlockStmt, CatchClause, ClassStmt, ExpressionStmt, FunctionStmt, IfStmt, PrintStmt, ReturnStmt, Stmt,
+    StatementProcessor, ThrowStmt, TryStmt, VarStmt, WhileStmt,
+};
+use serde_json::{json, Map, Value};
+use std::rc::Rc;
+
+// JSON form of the syntax tree for tools outside rlox. A program is an array of statements.
+// Every node is an object with a "kind", the "span" of source it was parsed from and its children
+// under named fields. Operators and names are tokens carrying their lexeme and source position.
+// A span may be left out of a node built by hand.
+//
+//   {"kind": "Binary", "operator": {"lexeme": "+", "line": 1, "column": 3},
+//    "span": {"start": {"line": 1, "column": 1}, "end": {"line": 1, "column": 6}},
+//    "left": {"kind": "Literal", "value": 1, "span": ...}, "right": {"kind": "Literal", "value": 2, "span": ...}}
+
+#[derive(Debug)]
+pub struct AstJsonError {
+    message: String,
+}
+
+impl AstJsonError {
+    fn new(message: String) -> AstJsonError {
+        AstJsonError { message }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+const BINARY_OPERATORS: &[TokenKind] = &[
+    BangEqual, EqualEqual, Greater, GreaterEqual, Less, LessEqual, Minus, Plus, Slash, Star,
+];
+
+pub fn to_json(statements: &[Stmt]) -> Value {
+    let mut writer = JsonWriter {};
+    Value::Array(statements.iter().map(|statement| statement.attach(&mut writer)).collect())
+}
+
+// A Lox number as JSON. Widening the f32 directly would write 45.67 as 45.66999816894531.
+pub fn number(value: f32) -> Value {
+    json!(value.to_string().parse::<f64>().unwrap())
+}
+
+pub fn from_json(value: &Value) -> Result<Vec<Stmt>, AstJsonError> {
+    value
+        .as_array()
+        .ok_or_else(|| AstJsonError::new(format!("Expected an array of statements, found {}", value)))?
+        .iter()
+        .map(statement)
+        .collect()
+}
+
+fn as_node(value: &Value) -> Result<&Map<String, Value>, AstJsonError> {
+    value
+        .as_object()
+        .ok_or_else(|| AstJsonError::new(format!("Expected a node object, found {}", value)))
+}
+
+fn statement(value: &Value) -> Result<Stmt, AstJsonError> {
+    let node = as_node(value)?;
+    let span = span(node)?;
+
+    match field(node, "kind")?.as_str() {
+        Some("Block") => Ok(Stmt::Block(BlockStmt { statements: statements(field(node, "statements")?)?, span })),
+        Some("Class") => {
+            let superclass = match field(node, "superclass")? {
+                Value::Null => None,
+                superclass => match expression(superclass)? {
+                    superclass @ Expr::Variable(_) => Some(superclass),
+                    _ => return Err(AstJsonError::new(format!("Superclass must be a Variable, found {}", superclass))),
+                },
+            };
+            let methods = array(field(node, "methods")?)?
+                .iter()
+                .map(|method| function(as_node(method)?))
+                .collect::<Result<Vec<Rc<FunctionStmt>>, AstJsonError>>()?;
+
+            Ok(Stmt::Class(ClassStmt { name: token(node, "name", &[Identifier])?, superclass, methods, span }))
+        }
+        Some("Expression") => Ok(Stmt::Expression(ExpressionStmt { expression: expression(field(node, "expression")?)?, span })),
+        Some("Function") => Ok(Stmt::Function(function(node)?)),
+        Some("If") => Ok(Stmt::If(IfStmt {
+            keyword: keyword(node, "if", &[If], span)?,
+            condition: expression(field(node, "condition")?)?,
+            then_branch: Box::new(statement(field(node, "then_branch")?)?),
+            else_branch: match field(node, "else_branch")? {
+                Value::Null => None,
+                else_branch => Some(Box::new(statement(else_branch)?)),
+            },
+            span,
+        })),
+        Some("Print") => Ok(Stmt::Print(PrintStmt { expression: expression(field(node, "expression")?)?, span })),
+        Some("Return") => Ok(Stmt::Return(ReturnStmt {
+            keyword: token(node, "keyword", &[Return])?,
+            value: optional_expression(field(node, "value")?)?,
+            span,
+        })),
+        Some("Throw") => Ok(Stmt::Throw(ThrowStmt {
+            keyword: token(node, "keyword", &[Throw])?,
+            value: expression(field(node, "value")?)?,
+            span,
+        })),
+        Some("Try") => {
+            let catch = match field(node, "catch")? {
+                Value::Null => None,
+                catch => {
+                    let catch = as_node(catch)?;
+                    Some(CatchClause { name: token(catch, "name", &[Identifier])?, body: statements(field(catch, "body")?)? })
+                }
+            };
+            let finally = match field(node, "finally")? {
+                Value::Null => None,
+                finally => Some(statements(finally)?),
+            };
+            if catch.is_none() && finally.is_none() {
+                return Err(AstJsonError::new("A Try needs a catch or a finally".to_string()));
+            }
+
+            Ok(Stmt::Try(TryStmt { body: statements(field(node, "body")?)?, catch, finally, span }))
+        }
+        Some("Var") => Ok(Stmt::Var(VarStmt {
+            name: token(node, "name", &[Identifier])?,
+            initializer: optional_expression(field(node, "initializer")?)?,
+            span,
+        })),
+        Some("While") => Ok(Stmt::While(WhileStmt {
+            keyword: keyword(node, "while", &[For, While], span)?,
+            condition: expression(field(node, "condition")?)?,
+            body: Box::new(statement(field(node, "body")?)?),
+            span,
+        })),
+        _ => Err(AstJsonError::new(format!("Unknown statement kind {}", node["kind"]))),
+    }
+}
+
+fn statements(value: &Value) -> Result<Vec<Stmt>, AstJsonError> {
+    array(value)?.iter().map(statement).collect()
+}
+
+fn function(node: &Map<String, Value>) -> Result<Rc<FunctionStmt>, AstJsonError> {
+    let params = array(field(node, "params")?)?
+        .iter()
+        .map(|param| rescan(param, &[Identifier]))
+        .collect::<Result<Vec<Token>, AstJsonError>>()?;
+
+    Ok(Rc::new(FunctionStmt {
+        name: token(node, "name", &[Identifier])?,
+        params,
+        body: statements(field(node, "body")?)?,
+        span: span(node)?,
+    }))
+}
+
+fn expression(value: &Value) -> Result<Expr, AstJsonError> {
+    let node = as_node(value)?;
+    let span = span(node)?;
+
+    match field(node, "kind")?.as_str() {
+        Some("Assign") => Ok(Expr::Assign(AssignExpr::new(
+            token(node, "name", &[Identifier])?,
+            expression(field(node, "value")?)?,
+            span,
+        ))),
+        Some("Binary") => Ok(Expr::Binary(BinaryExpr::new(
+            expression(field(node, "left")?)?,
+            token(node, "operator", BINARY_OPERATORS)?,
+            expression(field(node, "right")?)?,
+            span,
+        ))),
+        Some("Call") => Ok(Expr::Call(CallExpr::new(
+            expression(field(node, "callee")?)?,
+            token(node, "paren", &[RightParen])?,
+            array(field(node, "arguments")?)?.iter().map(expression).collect::<Result<Vec<Expr>, AstJsonError>>()?,
+            span,
+        ))),
+        Some("Get") => Ok(Expr::Get(GetExpr::new(
+            expression(field(node, "object")?)?,
+            token(node, "name", &[Identifier])?,
+            span,
+        ))),
+        Some("Grouping") => Ok(Expr::Grouping(GroupingExpr::new(expression(field(node, "expression")?)?, span))),
+        Some("Literal") => Ok(Expr::Literal(LiteralExpr { value: literal(field(node, "value")?)?, span })),
+        Some("Logical") => Ok(Expr::Logical(LogicalExpr::new(
+            expression(field(node, "left")?)?,
+            token(node, "operator", &[And, Or])?,
+            expression(field(node, "right")?)?,
+            span,
+        ))),
+        Some("Set") => Ok(Expr::Set(SetExpr::new(
+            expression(field(node, "object")?)?,
+            token(node, "name", &[Identifier])?,
+            expression(field(node, "value")?)?,
+            span,
+        ))),
+        Some("Super") => Ok(Expr::Super(SuperExpr {
+            keyword: token(node, "keyword", &[Super])?,
+            method: token(node, "method", &[Identifier])?,
+            span,
+        })),
+        Some("This") => Ok(Expr::This(ThisExpr { keyword: token(node, "keyword", &[This])?, span })),
+        Some("Unary") => Ok(Expr::Unary(UnaryExpr::new(
+            token(node, "operator", &[Bang, Minus])?,
+            expression(field(node, "right")?)?,
+            span,
+        ))),
+        Some("Variable") => Ok(Expr::Variable(VariableExpr { name: token(node, "name", &[Identifier])?, span })),
+        _ => Err(AstJsonError::new(format!("Unknown node kind {}", node["kind"]))),
+    }
+}
+
+fn optional_expression(value: &Value) -> Result<Option<Expr>, AstJsonError> {
+    match value {
+        Value::Null => Ok(None),
+        value => Ok(Some(expression(value)?)),
+    }
+}
+
+fn field<'a>(node: &'a Map<String, Value>, name: &str) -> Result<&'a Value, AstJsonError> {
+    node.get(name)
+        .ok_or_else(|| AstJsonError::new(format!("Node is missing \"{}\"", name)))
+}
+
+fn array(value: &Value) -> Result<&Vec<Value>, AstJsonError> {
+    value
+        .as_array()
+        .ok_or_else(|| AstJsonError::new(format!("Expected an array, found {}", value)))
+}
+
+fn literal(value: &Value) -> Result<LiteralValue, AstJsonError> {
+    match value {
+        Value::Bool(value) => Ok(LiteralValue::Boolean(*value)),
+        Value::Number(value) => Ok(LiteralValue::Number(value.as_f64().unwrap() as f32)),
+        Value::String(value) => Ok(LiteralValue::String(value.clone())),
+        Value::Null => Ok(LiteralValue::Nil),
+        _ => Err(AstJsonError::new(format!("Invalid literal value {}", value))),
+    }
+}
+
+fn span(node: &Map<String, Value>) -> Result<Span, AstJsonError> {
+    let span = match node.get("span") {
+        Some(span) => as_node(span)?,
+        None => return Ok(Span::default()),
+    };
+
+    Ok(Span::new(position(field(span, "start")?)?, position(field(span, "end")?)?))
+}
+
+fn position(value: &Value) -> Result<Position, AstJsonError> {
+    let number = |name| {
+        value[name]
+            .as_u64()
+            .map(|number| number as usize)
+            .ok_or_else(|| AstJsonError::new(format!("Expected a position, found {}", value)))
+    };
+
+    Ok(Position { line: number("line")?, column: number("column")? })
+}
+
+fn token(node: &Map<String, Value>, name: &str, allowed: &[TokenKind]) -> Result<Token, AstJsonError> {
+    rescan(field(node, name)?, allowed)
+}
+
+// Exports from before if and while statements kept their keyword don't have one, so it is made
+// up from where the statement starts
+fn keyword(node: &Map<String, Value>, lexeme: &str, allowed: &[TokenKind], span: Span) -> Result<Token, AstJsonError> {
+    match node.get("keyword") {
+        Some(keyword) => rescan(keyword, allowed),
+        None => rescan(&json!({ "lexeme": lexeme, "line": span.start.line, "column": span.start.column }), allowed),
+    }
+}
+
+// Rebuilds a token by rescanning its lexeme, so the token kinds stay defined in one place
+fn rescan(token: &Value, allowed: &[TokenKind]) -> Result<Token, AstJsonError> {
+    let lexeme = token["lexeme"]
+        .as_str()
+        .ok_or_else(|| AstJsonError::new("Token is missing \"lexeme\"".to_string()))?;
+    let line = token["line"].as_u64().unwrap_or(0) as usize;
+    let column = token["column"].as_u64().unwrap_or(0) as usize;
+
+    match Scanner::new(lexeme.to_string()).next() {
+        Some(Ok(token)) if token.lexeme() == lexeme && allowed.contains(&token.kind()) => {
+            Ok(Token::new(token.kind(), lexeme.to_string(), None, line, column))
+        }
+        _ => Err(AstJsonError::new(format!("Invalid token \"{}\"", lexeme))),
+    }
+}
+
+struct JsonWriter {}
+
+impl JsonWriter {
+    fn token(token: &Token) -> Value {
+        json!({ "lexeme": token.lexeme(), "line": token.line(), "column": token.column() })
+    }
+
+    fn span(span: &Span) -> Value {
+        json!({
+            "start": { "line": span.start.line, "column": span.start.column },
+            "end": { "line": span.end.line, "column": span.end.column },
+        })
+    }
+
+    fn optional(&mut self, expression: &Option<Expr>) -> Value {
+        match expression {
+            Some(expression) => expression.attach(self),
+            None => Value::Null,
+        }
+    }
+
+    fn statements(&mut self, statements: &[Stmt]) -> Value {
+        Value::Array(statements.iter().map(|statement| statement.attach(self)).collect())
+    }
+}
+
+impl ExpressionProcessor<Value> for JsonWriter {
+    fn process_assign_expr(&mut self, assign_expr: &AssignExpr) -> Value {
+        json!({
+            "kind": "Assign",
+            "span": JsonWriter::span(&assign_expr.span),
+            "name": JsonWriter::token(&assign_expr.name),
+            "value": assign_expr.value.attach(self),
+        })
+    }
+
+    fn process_unary_expr(&mut self, unary_expr: &UnaryExpr) -> Value {
+        json!({
+            "kind": "Unary",
+            "span": JsonWriter::span(&unary_expr.span),
+            "operator": JsonWriter::token(&unary_expr.operator),
+            "right": unary_expr.right.attach(self),
+        })
+    }
+
+    fn process_binary_expr(&mut self, binary_expr: &BinaryExpr) -> Value {
+        json!({
+            "kind": "Binary",
+            "span": JsonWriter::span(&binary_expr.span),
+            "operator": JsonWriter::token(&binary_expr.operator),
+            "left": binary_expr.left.attach(self),
+            "right": binary_expr.right.attach(self),
+        })
+    }
+
+    fn process_call_expr(&mut self, call_expr: &CallExpr) -> Value {
+        let arguments: Vec<Value> = call_expr.arguments.iter().map(|argument| argument.attach(self)).collect();
+
+        json!({
+            "kind": "Call",
+            "span": JsonWriter::span(&call_expr.span),
+            "callee": call_expr.callee.attach(self),
+            "paren": JsonWriter::token(&call_expr.paren),
+            "arguments": arguments,
+        })
+    }
+
+    fn process_get_expr(&mut self, get_expr: &GetExpr) -> Value {
+        json!({
+            "kind": "Get",
+            "span": JsonWriter::span(&get_expr.span),
+            "object": get_expr.object.attach(self),
+            "name": JsonWriter::token(&get_expr.name),
+        })
+    }
+
+    fn process_grouping_expr(&mut self, grouping_expr: &GroupingExpr) -> Value {
+        json!({
+            "kind": "Grouping",
+            "span": JsonWriter::span(&grouping_expr.span),
+            "expression": grouping_expr.expression.attach(self),
+        })
+    }
+
+    fn process_literal_expr(&mut self, literal_expr: &LiteralExpr) -> Value {
+        let value = match &literal_expr.value {
+            LiteralValue::Boolean(value) => json!(value),
+            LiteralValue::Number(value) => number(*value),
+            LiteralValue::String(value) => json!(value),
+            LiteralValue::Nil => Value::Null,
+        };
+
+        json!({
+            "kind": "Literal",
+            "span": JsonWriter::span(&literal_expr.span),
+            "value": value,
+        })
+    }
+
+    fn process_logical_expr(&mut self, logical_expr: &LogicalExpr) -> Value {
+        json!({
+            "kind": "Logical",
+            "span": JsonWriter::span(&logical_expr.span),
+            "operator": JsonWriter::token(&logical_expr.operator),
+            "left": logical_expr.left.attach(self),
+            "right": logical_expr.right.attach(self),
+        })
+    }
+
+    fn process_set_expr(&mut self, set_expr: &SetExpr) -> Value {
+        json!({
+            "kind": "Set",
+            "span": JsonWriter::span(&set_expr.span),
+            "object": set_expr.object.attach(self),
+            "name": JsonWriter::token(&set_expr.name),
+            "value": set_expr.value.attach(self),
+        })
+    }
+
+    fn process_super_expr(&mut self, super_expr: &SuperExpr) -> Value {
+        json!({
+            "kind": "Super",
+            "span": JsonWriter::span(&super_expr.span),
+            "keyword": JsonWriter::token(&super_expr.keyword),
+            "method": JsonWriter::token(&super_expr.method),
+        })
+    }
+
+    fn process_this_expr(&mut self, this_expr: &ThisExpr) -> Value {
+        json!({
+            "kind": "This",
+            "span": JsonWriter::span(&this_expr.span),
+            "keyword": JsonWriter::token(&this_expr.keyword),
+        })
+    }
+
+    fn process_variable_expr(&mut self, variable_expr: &VariableExpr) -> Value {
+        json!({
+            "kind": "Variable",
+            "span": JsonWriter::span(&variable_expr.span),
+            "name": JsonWriter::token(&variable_expr.name),
+        })
+    }
+}
+
+impl StatementProcessor<Value> for JsonWriter {
+    fn process_block_stmt(&mut self, block_stmt: &BlockStmt) -> Value {
+        json!({
+            "kind": "Block",
+            "span": JsonWriter::span(&block_stmt.span),
+            "statements": self.statements(&block_stmt.statements),
+        })
+    }
+
+    fn process_class_stmt(&mut self, class_stmt: &ClassStmt) -> Value {
+        let methods: Vec<Value> = class_stmt.methods.iter().map(|method| self.process_function_stmt(method)).collect();
+
+        json!({
+            "kind": "Class",
+            "span": JsonWriter::span(&class_stmt.span),
+            "name": JsonWriter::token(&class_stmt.name),
+            "superclass": self.optional(&class_stmt.superclass),
+            "methods": methods,
+        })
+    }
+
+    fn process_expression_stmt(&mut self, expression_stmt: &ExpressionStmt) -> Value {
+        json!({
+            "kind": "Expression",
+            "span": JsonWriter::span(&expression_stmt.span),
+            "expression": expression_stmt.expression.attach(self),
+        })
+    }
+
+    fn process_function_stmt(&mut self, function_stmt: &Rc<FunctionStmt>) -> Value {
+        let params: Vec<Value> = function_stmt.params.iter().map(JsonWriter::token).collect();
+
+        json!({
+            "kind": "Function",
+            "span": JsonWriter::span(&function_stmt.span),
+            "name": JsonWriter::token(&function_stmt.name),
+            "params": params,
+            "body": self.statements(&function_stmt.body),
+        })
+    }
+
+    fn process_if_stmt(&mut self, if_stmt: &IfStmt) -> Value {
+        let else_branch = match &if_stmt.else_branch {
+            Some(else_branch) => else_branch.attach(self),
+            None => Value::Null,
+        };
+
+        json!({
+            "kind": "If",
+            "span": JsonWriter::span(&if_stmt.span),
+            "keyword": JsonWriter::token(&if_stmt.keyword),
+            "condition": if_stmt.condition.attach(self),
+            "then_branch": if_stmt.then_branch.attach(self),
+            "else_branch": else_branch,
+        })
+    }
+
+    fn process_print_stmt(&mut self, print_stmt: &PrintStmt) -> Value {
+        json!({
+            "kind": "Print",
+            "span": JsonWriter::span(&print_stmt.span),
+            "expression": print_stmt.expression.attach(self),
+        })
+    }
+
+    fn process_return_stmt(&mut self, return_stmt: &ReturnStmt) -> Value {
+        json!({
+            "kind": "Return",
+            "span": JsonWriter::span(&return_stmt.span),
+            "keyword": JsonWriter::token(&return_stmt.keyword),
+            "value": self.optional(&return_stmt.value),
+        })
+    }
+
+    fn process_throw_stmt(&mut self, throw_stmt: &ThrowStmt) -> Value {
+        json!({
+            "kind": "Throw",
+            "span": JsonWriter::span(&throw_stmt.span),
+            "keyword": JsonWriter::token(&throw_stmt.keyword),
+            "value": throw_stmt.value.attach(self),
+        })
+    }
+
+    fn process_try_stmt(&mut self, try_stmt: &TryStmt) -> Value {
+        let catch = match &try_stmt.catch {
+            Some(catch) => json!({ "name": JsonWriter::token(&catch.name), "body": self.statements(&catch.body) }),
+            None => Value::Null,
+        };
+        let finally = match &try_stmt.finally {
+            Some(finally) => self.statements(finally),
+            None => Value::Null,
+        };
+
+        json!({
+            "kind": "Try",
+            "span": JsonWriter::span(&try_stmt.span),
+            "body": self.statements(&try_stmt.body),
+            "catch": catch,
+            "finally": finally,
+        })
+    }
+
+    fn process_var_stmt(&mut self, var_stmt: &VarStmt) -> Value {
+        json!({
+            "kind": "Var",
+            "span": JsonWriter::span(&var_stmt.span),
+            "name": JsonWriter::token(&var_stmt.name),
+            "initializer": self.optional(&var_stmt.initializer),
+        })
+    }
+
+    fn process_while_stmt(&mut self, while_stmt: &WhileStmt) -> Value {
+        json!({
+            "kind": "While",
+            "span": JsonWriter::span(&while_stmt.span),
+            "keyword": JsonWriter::token(&while_stmt.keyword),
+            "condition": while_stmt.condition.attach(self),
+            "body": while_stmt.body.attach(self),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn parse(source: &str) -> Vec<Stmt> {
+        Parser::new(Scanner::new(source.to_string())).parse().expect("syntax error")
+    }
+
+    #[test]
+    fn every_node_has_a_span() {
+        let json = to_json(&parse("print (1 +\n  \"a\nb\");"));
+
+        let print = &json[0];
+        assert_eq!(print["span"], json!({ "start": { "line": 1, "column": 1 }, "end": { "line": 3, "column": 5 } }));
+
+        let grouping = &print["expression"];
+        assert_eq!(grouping["span"], json!({ "start": { "line": 1, "column": 7 }, "end": { "line": 3, "column": 4 } }));
+
+        let binary = &grouping["expression"];
+        assert_eq!(binary["span"], json!({ "start": { "line": 1, "column": 8 }, "end": { "line": 3, "column": 3 } }));
+        assert_eq!(binary["left"]["span"], json!({ "start": { "line": 1, "column": 8 }, "end": { "line": 1, "column": 9 } }));
+        assert_eq!(binary["right"]["span"], json!({ "start": { "line": 2, "column": 3 }, "end": { "line": 3, "column": 3 } }));
+    }
+
+    #[test]
+    fn spans_round_trip() {
+        let source = "class A < B { m(x) { return (x + 1) * -2; } }\n\
+                      for (var i = 0; i < 3; i = i + 1) { if (i) print i; else print nil; }\n\
+                      try { throw A().m(1); } catch (e) { print e.message; } finally { this; }\n\
+                      while (true) { a.b = c or d; }";
+        let json = to_json(&parse(source));
+
+        let statements = from_json(&json).expect("invalid AST");
+        assert_eq!(to_json(&statements), json);
+    }
+
+    #[test]
+    fn span_may_be_left_out() {
+        let json = json!([{ "kind": "Print", "expression": { "kind": "Literal", "value": 1 } }]);
+
+        let statements = from_json(&json).expect("invalid AST");
+        assert_eq!(statements[0].span(), Span::default());
+    }
+
+    #[test]
+    fn keyword_may_be_left_out() {
+        let start = json!({ "line": 3, "column": 5 });
+        let span = json!({ "start": start, "end": { "line": 3, "column": 20 } });
+        let condition = json!({ "kind": "Literal", "value": true });
+        let body = json!({ "kind": "Block", "statements": [] });
+        let json = json!([
+            { "kind": "If", "span": span, "condition": condition, "then_branch": body, "else_branch": null },
+            { "kind": "While", "span": span, "condition": condition, "body": body },
+        ]);
+
+        let statements = from_json(&json).expect("invalid AST");
+        assert_eq!(statements[0].line(), Some(3));
+        assert_eq!(statements[1].line(), Some(3));
+        assert_eq!(to_json(&statements)[1]["keyword"], json!({ "lexeme": "while", "line": 3, "column": 5 }));
+    }
+}
@@ -0,0 +1,408 @@
+use crate::expr::{
+    AssignExpr, BinaryExpr, CallExpr, Expr, ExpressionProcessor, GetExpr, GroupingExpr, LiteralExpr, LiteralValue,
+    LogicalExpr, SetExpr, SuperExpr, ThisExpr, UnaryExpr, VariableExpr,
+};
+use crate::stmt::{
+    BlockStmt, ClassStmt, ExpressionStmt, FunctionStmt, IfStmt, PrintStmt, ReturnStmt, Stmt, StatementProcessor,
+    ThrowStmt, TryStmt, VarStmt, WhileStmt,
+};
+use std::rc::Rc;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PrintStyle {
+    // Fully parenthesized prefix form, e.g. (* (- 123) (group 45.67)), which makes the
+    // parser's precedence and associativity decisions visible
+    Lisp,
+    // Lox source with normalized spacing
+    Source,
+}
+
+pub struct AstPrinter {
+    style: PrintStyle,
+    // Nesting depth of the statement being printed, for indenting Source output
+    depth: usize,
+}
+
+impl AstPrinter {
+    pub fn new(style: PrintStyle) -> AstPrinter {
+        AstPrinter { style, depth: 0 }
+    }
+
+    pub fn print(&mut self, expression: &Expr) -> String {
+        expression.attach(self)
+    }
+
+    // One statement per line
+    pub fn print_program(&mut self, statements: &[Stmt]) -> String {
+        statements.iter().map(|statement| statement.attach(self)).collect::<Vec<String>>().join("\n")
+    }
+
+    fn parenthesize(&mut self, name: &str, expressions: &[&Expr]) -> String {
+        let mut out = format!("({}", name);
+
+        for expression in expressions {
+            out.push(' ');
+            out.push_str(&expression.attach(self));
+        }
+
+        out.push(')');
+        out
+    }
+
+    // Statements are joined on one line in Lisp style and indented on their own lines in Source style
+    fn nested(&mut self, statements: &[Stmt]) -> String {
+        match self.style {
+            PrintStyle::Lisp => statements.iter().map(|statement| format!(" {}", statement.attach(self))).collect(),
+            PrintStyle::Source => {
+                self.depth += 1;
+                let indent = "    ".repeat(self.depth);
+                let body: String = statements
+                    .iter()
+                    .map(|statement| format!("\n{}{}", indent, statement.attach(self)))
+                    .collect();
+                self.depth -= 1;
+
+                if body.is_empty() {
+                    "{}".to_string()
+                } else {
+                    format!("{{{}\n{}}}", body, "    ".repeat(self.depth))
+                }
+            }
+        }
+    }
+
+    fn function(&mut self, function_stmt: &FunctionStmt, keyword: &str) -> String {
+        let params: Vec<&str> = function_stmt.params.iter().map(|param| param.lexeme()).collect();
+        let body = self.nested(&function_stmt.body);
+
+        match self.style {
+            PrintStyle::Lisp => format!("({} {} ({}){})", keyword, function_stmt.name.lexeme(), params.join(" "), body),
+            PrintStyle::Source => {
+                let keyword = if keyword == "fun" { "fun " } else { "" };
+                format!("{}{}({}) {}", keyword, function_stmt.name.lexeme(), params.join(", "), body)
+            }
+        }
+    }
+
+    // A branch or loop body that is not a block goes on its own line in Source style
+    fn body(&mut self, statement: &Stmt) -> String {
+        match (self.style, statement) {
+            (PrintStyle::Source, Stmt::Block(_)) => format!(" {}", statement.attach(self)),
+            (PrintStyle::Source, _) => {
+                self.depth += 1;
+                let body = format!("\n{}{}", "    ".repeat(self.depth), statement.attach(self));
+                self.depth -= 1;
+                body
+            }
+            (PrintStyle::Lisp, _) => format!(" {}", statement.attach(self)),
+        }
+    }
+}
+
+impl ExpressionProcessor<String> for AstPrinter {
+    fn process_assign_expr(&mut self, assign_expr: &AssignExpr) -> String {
+        let value = assign_expr.value.attach(self);
+
+        match self.style {
+            PrintStyle::Lisp => format!("(= {} {})", assign_expr.name.lexeme(), value),
+            PrintStyle::Source => format!("{} = {}", assign_expr.name.lexeme(), value),
+        }
+    }
+
+    fn process_unary_expr(&mut self, unary_expr: &UnaryExpr) -> String {
+        match self.style {
+            PrintStyle::Lisp => self.parenthesize(unary_expr.operator.lexeme(), &[&unary_expr.right]),
+            PrintStyle::Source => format!("{}{}", unary_expr.operator.lexeme(), unary_expr.right.attach(self)),
+        }
+    }
+
+    fn process_binary_expr(&mut self, binary_expr: &BinaryExpr) -> String {
+        match self.style {
+            PrintStyle::Lisp => {
+                self.parenthesize(binary_expr.operator.lexeme(), &[&binary_expr.left, &binary_expr.right])
+            }
+            PrintStyle::Source => {
+                let left = binary_expr.left.attach(self);
+                let right = binary_expr.right.attach(self);
+                format!("{} {} {}", left, binary_expr.operator.lexeme(), right)
+            }
+        }
+    }
+
+    fn process_call_expr(&mut self, call_expr: &CallExpr) -> String {
+        match self.style {
+            PrintStyle::Lisp => {
+                let mut expressions = vec![call_expr.callee.as_ref()];
+                expressions.extend(call_expr.arguments.iter());
+                self.parenthesize("call", &expressions)
+            }
+            PrintStyle::Source => {
+                let callee = call_expr.callee.attach(self);
+                let arguments: Vec<String> = call_expr.arguments.iter().map(|argument| argument.attach(self)).collect();
+                format!("{}({})", callee, arguments.join(", "))
+            }
+        }
+    }
+
+    fn process_get_expr(&mut self, get_expr: &GetExpr) -> String {
+        let object = get_expr.object.attach(self);
+
+        match self.style {
+            PrintStyle::Lisp => format!("(. {} {})", object, get_expr.name.lexeme()),
+            PrintStyle::Source => format!("{}.{}", object, get_expr.name.lexeme()),
+        }
+    }
+
+    fn process_grouping_expr(&mut self, grouping_expr: &GroupingExpr) -> String {
+        match self.style {
+            PrintStyle::Lisp => self.parenthesize("group", &[&grouping_expr.expression]),
+            PrintStyle::Source => format!("({})", grouping_expr.expression.attach(self)),
+        }
+    }
+
+    fn process_literal_expr(&mut self, literal_expr: &LiteralExpr) -> String {
+        match &literal_expr.value {
+            LiteralValue::Boolean(value) => value.to_string(),
+            LiteralValue::Number(value) => value.to_string(),
+            LiteralValue::String(value) => format!("\"{}\"", value),
+            LiteralValue::Nil => "nil".to_string(),
+        }
+    }
+
+    fn process_logical_expr(&mut self, logical_expr: &LogicalExpr) -> String {
+        match self.style {
+            PrintStyle::Lisp => {
+                self.parenthesize(logical_expr.operator.lexeme(), &[&logical_expr.left, &logical_expr.right])
+            }
+            PrintStyle::Source => {
+                let left = logical_expr.left.attach(self);
+                let right = logical_expr.right.attach(self);
+                format!("{} {} {}", left, logical_expr.operator.lexeme(), right)
+            }
+        }
+    }
+
+    fn process_set_expr(&mut self, set_expr: &SetExpr) -> String {
+        let object = set_expr.object.attach(self);
+        let value = set_expr.value.attach(self);
+
+        match self.style {
+            PrintStyle::Lisp => format!("(= (. {} {}) {})", object, set_expr.name.lexeme(), value),
+            PrintStyle::Source => format!("{}.{} = {}", object, set_expr.name.lexeme(), value),
+        }
+    }
+
+    fn process_super_expr(&mut self, super_expr: &SuperExpr) -> String {
+        match self.style {
+            PrintStyle::Lisp => format!("(super {})", super_expr.method.lexeme()),
+            PrintStyle::Source => format!("super.{}", super_expr.method.lexeme()),
+        }
+    }
+
+    fn process_this_expr(&mut self, _this_expr: &ThisExpr) -> String {
+        "this".to_string()
+    }
+
+    fn process_variable_expr(&mut self, variable_expr: &VariableExpr) -> String {
+        variable_expr.name.lexeme().to_string()
+    }
+}
+
+impl StatementProcessor<String> for AstPrinter {
+    fn process_block_stmt(&mut self, block_stmt: &BlockStmt) -> String {
+        let body = self.nested(&block_stmt.statements);
+
+        match self.style {
+            PrintStyle::Lisp => format!("(block{})", body),
+            PrintStyle::Source => body,
+        }
+    }
+
+    fn process_class_stmt(&mut self, class_stmt: &ClassStmt) -> String {
+        let superclass = class_stmt.superclass.as_ref().map(|superclass| superclass.attach(self));
+        let name = class_stmt.name.lexeme();
+
+        match self.style {
+            PrintStyle::Lisp => {
+                let mut out = format!("(class {}", name);
+                if let Some(superclass) = superclass {
+                    out.push_str(&format!(" < {}", superclass));
+                }
+                for method in &class_stmt.methods {
+                    out.push(' ');
+                    out.push_str(&self.function(method, "method"));
+                }
+                out.push(')');
+                out
+            }
+            PrintStyle::Source => {
+                let mut out = match superclass {
+                    Some(superclass) => format!("class {} < {} {{", name, superclass),
+                    None => format!("class {} {{", name),
+                };
+
+                self.depth += 1;
+                for method in &class_stmt.methods {
+                    let method = self.function(method, "method");
+                    out.push_str(&format!("\n{}{}", "    ".repeat(self.depth), method));
+                }
+                self.depth -= 1;
+
+                if class_stmt.methods.is_empty() {
+                    out.push('}');
+                } else {
+                    out.push_str(&format!("\n{}}}", "    ".repeat(self.depth)));
+                }
+                out
+            }
+        }
+    }
+
+    fn process_expression_stmt(&mut self, expression_stmt: &ExpressionStmt) -> String {
+        let expression = expression_stmt.expression.attach(self);
+
+        match self.style {
+            PrintStyle::Lisp => format!("(; {})", expression),
+            PrintStyle::Source => format!("{};", expression),
+        }
+    }
+
+    fn process_function_stmt(&mut self, function_stmt: &Rc<FunctionStmt>) -> String {
+        self.function(function_stmt, "fun")
+    }
+
+    fn process_if_stmt(&mut self, if_stmt: &IfStmt) -> String {
+        let condition = if_stmt.condition.attach(self);
+        let then_branch = self.body(&if_stmt.then_branch);
+        let else_branch = if_stmt.else_branch.as_ref().map(|else_branch| self.body(else_branch));
+
+        match (self.style, else_branch) {
+            (PrintStyle::Lisp, Some(else_branch)) => format!("(if {}{}{})", condition, then_branch, else_branch),
+            (PrintStyle::Lisp, None) => format!("(if {}{})", condition, then_branch),
+            (PrintStyle::Source, Some(else_branch)) => {
+                let separator = if matches!(*if_stmt.then_branch, Stmt::Block(_)) {
+                    " ".to_string()
+                } else {
+                    format!("\n{}", "    ".repeat(self.depth))
+                };
+                format!("if ({}){}{}else{}", condition, then_branch, separator, else_branch)
+            }
+            (PrintStyle::Source, None) => format!("if ({}){}", condition, then_branch),
+        }
+    }
+
+    fn process_print_stmt(&mut self, print_stmt: &PrintStmt) -> String {
+        let expression = print_stmt.expression.attach(self);
+
+        match self.style {
+            PrintStyle::Lisp => format!("(print {})", expression),
+            PrintStyle::Source => format!("print {};", expression),
+        }
+    }
+
+    fn process_return_stmt(&mut self, return_stmt: &ReturnStmt) -> String {
+        let value = return_stmt.value.as_ref().map(|value| value.attach(self));
+
+        match (self.style, value) {
+            (PrintStyle::Lisp, Some(value)) => format!("(return {})", value),
+            (PrintStyle::Lisp, None) => "(return)".to_string(),
+            (PrintStyle::Source, Some(value)) => format!("return {};", value),
+            (PrintStyle::Source, None) => "return;".to_string(),
+        }
+    }
+
+    fn process_throw_stmt(&mut self, throw_stmt: &ThrowStmt) -> String {
+        let value = throw_stmt.value.attach(self);
+
+        match self.style {
+            PrintStyle::Lisp => format!("(throw {})", value),
+            PrintStyle::Source => format!("throw {};", value),
+        }
+    }
+
+    fn process_try_stmt(&mut self, try_stmt: &TryStmt) -> String {
+        let body = self.nested(&try_stmt.body);
+        let catch = try_stmt.catch.as_ref().map(|catch| (catch.name.lexeme(), self.nested(&catch.body)));
+        let finally = try_stmt.finally.as_ref().map(|finally| self.nested(finally));
+
+        match self.style {
+            PrintStyle::Lisp => {
+                let mut out = format!("(try{}", body);
+                if let Some((name, body)) = catch {
+                    out.push_str(&format!(" (catch {}{})", name, body));
+                }
+                if let Some(body) = finally {
+                    out.push_str(&format!(" (finally{})", body));
+                }
+                out.push(')');
+                out
+            }
+            PrintStyle::Source => {
+                let mut out = format!("try {}", body);
+                if let Some((name, body)) = catch {
+                    out.push_str(&format!(" catch ({}) {}", name, body));
+                }
+                if let Some(body) = finally {
+                    out.push_str(&format!(" finally {}", body));
+                }
+                out
+            }
+        }
+    }
+
+    fn process_var_stmt(&mut self, var_stmt: &VarStmt) -> String {
+        let initializer = var_stmt.initializer.as_ref().map(|initializer| initializer.attach(self));
+        let name = var_stmt.name.lexeme();
+
+        match (self.style, initializer) {
+            (PrintStyle::Lisp, Some(initializer)) => format!("(var {} {})", name, initializer),
+            (PrintStyle::Lisp, None) => format!("(var {})", name),
+            (PrintStyle::Source, Some(initializer)) => format!("var {} = {};", name, initializer),
+            (PrintStyle::Source, None) => format!("var {};", name),
+        }
+    }
+
+    fn process_while_stmt(&mut self, while_stmt: &WhileStmt) -> String {
+        let condition = while_stmt.condition.attach(self);
+        let body = self.body(&while_stmt.body);
+
+        match self.style {
+            PrintStyle::Lisp => format!("(while {}{})", condition, body),
+            PrintStyle::Source => format!("while ({}){}", condition, body),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::{Scanner, Span, Token, TokenKind};
+
+    fn number(value: f32) -> Expr {
+        Expr::Literal(LiteralExpr { value: LiteralValue::Number(value), span: Span::default() })
+    }
+
+    #[test]
+    fn prints_the_example_from_the_book() {
+        // -123 * (45.67)
+        let minus = Token::new(TokenKind::Minus, "-".to_string(), None, 1, 1);
+        let star = Token::new(TokenKind::Star, "*".to_string(), None, 1, 6);
+        let expression = Expr::Binary(BinaryExpr::new(
+            Expr::Unary(UnaryExpr::new(minus, number(123.0), Span::default())),
+            star,
+            Expr::Grouping(GroupingExpr::new(number(45.67), Span::default())),
+            Span::default(),
+        ));
+
+        assert_eq!(AstPrinter::new(PrintStyle::Lisp).print(&expression), "(* (- 123) (group 45.67))");
+        assert_eq!(AstPrinter::new(PrintStyle::Source).print(&expression), "-123 * (45.67)");
+    }
+
+    #[test]
+    fn prints_parsed_source() {
+        let statements = Parser::new(Scanner::new("print -123 * (45.67);".to_string())).parse().unwrap();
+
+        assert_eq!(AstPrinter::new(PrintStyle::Lisp).print_program(&statements), "(print (* (- 123) (group 45.67)))");
+    }
+}
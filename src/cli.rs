@@ -0,0 +1,397 @@
+// Command line parsing for the rlox binary
+
+use rlox::Permissions;
+use std::time::Duration;
+
+pub const USAGE: &str = "\
+Usage: rlox [command] [options] [script | -] [script args...]
+
+Commands:
+  run      Run a script (the default when a script is given)
+  repl     Start an interactive prompt (the default with no arguments)
+  check    Parse a script and report syntax errors without running it
+  fmt      Print a script with normalized formatting
+  test     Run scripts and compare their output to `// expect: ` comments
+  tokens   Print the tokens of a script
+  ast      Print the syntax tree of a script
+
+Options:
+  -e, --eval <code>   Use <code> as the script
+  --json              tokens, ast: print as JSON
+  --source            ast: print as normalized Lox source
+  --cst               ast: print the concrete syntax tree
+  --ast-json          run: the script is an AST exported with `ast --json`
+  --max-steps <n>     run: stop the script after <n> statements and expressions
+  --max-allocation <n>
+                      run: stop the script once it has allocated <n> bytes in all,
+                      counting memory that has since been freed
+  --timeout <secs>    run: stop the script once it has run for <secs> seconds
+  --allow-read[=<paths>]
+                      run: let the script read files, or only those under the
+                      comma separated paths
+  --allow-write[=<paths>]
+                      run: let the script write files, or only those under the paths
+  --allow-env         run: let the script read environment variables
+  --allow-run         run: let the script run other programs
+  --allow-all         run: let the script do all of the above
+  --deny-clock        run: don't let the script read the clock
+  -h, --help          Print this message
+  -V, --version       Print the version
+
+A script of `-` is read from standard input. Arguments after the script are passed to it.";
+
+#[derive(Debug, PartialEq)]
+pub enum Source {
+    File(String),
+    Stdin,
+    Eval(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AstFormat {
+    Lisp,
+    Source,
+    Json,
+    Cst,
+}
+
+// Limits on a script being run, and what it may do outside the interpreter
+#[derive(Debug, Default, PartialEq)]
+pub struct RunOptions {
+    pub max_steps: Option<u64>,
+    pub max_allocation: Option<usize>,
+    pub timeout: Option<Duration>,
+    pub permissions: Permissions,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Run { source: Source, args: Vec<String>, options: RunOptions },
+    RunAstJson { path: String, args: Vec<String>, options: RunOptions },
+    Repl,
+    Check { source: Source },
+    Fmt { source: Source },
+    Test { paths: Vec<String> },
+    Tokens { source: Source, json: bool },
+    Ast { source: Source, format: AstFormat },
+    Help,
+    Version,
+}
+
+// Options gathered before the script name. Anything after the script belongs to the script.
+struct Options {
+    eval: Option<String>,
+    json: bool,
+    source: bool,
+    cst: bool,
+    ast_json: bool,
+    run: RunOptions,
+    positional: Vec<String>,
+}
+
+pub fn parse_args(args: &[String]) -> Result<Command, String> {
+    let mut args = args.iter().skip(1).peekable();
+
+    let command = match args.peek().map(|arg| arg.as_str()) {
+        None => return Ok(Command::Repl),
+        Some(name @ ("run" | "repl" | "check" | "fmt" | "test" | "tokens" | "ast")) => {
+            args.next();
+            name.to_string()
+        }
+        // Spellings from before there were subcommands
+        Some(
+            "--tokens" | "--tokens=json" | "--dump-ast" | "--dump-ast=source" | "--dump-cst" | "--emit=ast-json"
+            | "--load=ast-json",
+        ) => return legacy_flag(args.cloned().collect()),
+        Some(_) => "run".to_string(),
+    };
+
+    let mut options = Options {
+        eval: None,
+        json: false,
+        source: false,
+        cst: false,
+        ast_json: false,
+        run: RunOptions::default(),
+        positional: vec![],
+    };
+
+    while let Some(arg) = args.next() {
+        if !options.positional.is_empty() || (options.eval.is_some() && command == "run") {
+            options.positional.push(arg.clone());
+            continue;
+        }
+
+        // Options that take a value accept it either as the next argument or after an `=`
+        let (arg, mut inline) = match arg.split_once('=') {
+            Some((name, value)) if arg.starts_with("--") => (name, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        let mut value = |args: &mut dyn Iterator<Item = &String>| match inline.take() {
+            Some(value) => Ok(value),
+            None => args.next().cloned().ok_or_else(|| format!("{} needs an argument", arg)),
+        };
+
+        match arg {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-V" | "--version" => return Ok(Command::Version),
+            "-e" | "--eval" => options.eval = Some(value(&mut args)?),
+            "--max-steps" => options.run.max_steps = Some(number(arg, &value(&mut args)?)?),
+            "--max-allocation" => options.run.max_allocation = Some(number(arg, &value(&mut args)?)? as usize),
+            "--timeout" => options.run.timeout = Some(seconds(arg, &value(&mut args)?)?),
+            "--allow-read" => options.run.permissions = match inline.as_deref().map(|paths| path_list(arg, paths)).transpose()? {
+                Some(paths) => paths.iter().fold(options.run.permissions, |permissions, path| permissions.allow_read(path)),
+                None => options.run.permissions.allow_read_all(),
+            },
+            "--allow-write" => options.run.permissions = match inline.as_deref().map(|paths| path_list(arg, paths)).transpose()? {
+                Some(paths) => paths.iter().fold(options.run.permissions, |permissions, path| permissions.allow_write(path)),
+                None => options.run.permissions.allow_write_all(),
+            },
+            "--allow-env" => options.run.permissions = options.run.permissions.allow_env(),
+            "--allow-run" => options.run.permissions = options.run.permissions.allow_spawn(),
+            "--allow-all" => options.run.permissions = Permissions::all(),
+            "--deny-clock" => options.run.permissions = options.run.permissions.deny_clock(),
+            "--json" => options.json = true,
+            "--source" => options.source = true,
+            "--cst" => options.cst = true,
+            "--ast-json" => options.ast_json = true,
+            "-" => options.positional.push(arg.to_string()),
+            _ if arg.starts_with('-') => return Err(format!("Unknown option {}", arg)),
+            _ => options.positional.push(arg.to_string()),
+        }
+    }
+
+    build(&command, options)
+}
+
+fn build(command: &str, mut options: Options) -> Result<Command, String> {
+    match command {
+        "repl" => Ok(Command::Repl),
+        "test" if options.positional.is_empty() => Err("test needs at least one script or directory".to_string()),
+        "test" => Ok(Command::Test { paths: options.positional }),
+        "run" if options.ast_json => {
+            if options.positional.is_empty() {
+                return Err("--ast-json needs a file".to_string());
+            }
+            let path = options.positional.remove(0);
+            Ok(Command::RunAstJson { path, args: options.positional, options: options.run })
+        }
+        _ => {
+            let source = take_source(&mut options)?;
+
+            match command {
+                "run" => Ok(Command::Run { source, args: options.positional, options: options.run }),
+                "check" => Ok(Command::Check { source }),
+                "fmt" => Ok(Command::Fmt { source }),
+                "tokens" => Ok(Command::Tokens { source, json: options.json }),
+                _ => {
+                    let format = if options.json {
+                        AstFormat::Json
+                    } else if options.source {
+                        AstFormat::Source
+                    } else if options.cst {
+                        AstFormat::Cst
+                    } else {
+                        AstFormat::Lisp
+                    };
+
+                    Ok(Command::Ast { source, format })
+                }
+            }
+        }
+    }
+}
+
+fn number(option: &str, value: &str) -> Result<u64, String> {
+    value.parse().map_err(|_| format!("{} needs a whole number, not {}", option, value))
+}
+
+// An empty path would stand for the current directory, so `--allow-read=` is refused rather
+// than quietly allowing everything under it
+fn path_list(option: &str, value: &str) -> Result<Vec<String>, String> {
+    let paths: Vec<String> = value.split(',').map(String::from).collect();
+
+    if paths.iter().any(|path| path.is_empty()) {
+        return Err(format!("{} needs a comma separated list of paths, not \"{}\"", option, value));
+    }
+
+    Ok(paths)
+}
+
+fn seconds(option: &str, value: &str) -> Result<Duration, String> {
+    match value.parse::<f64>() {
+        Ok(seconds) if seconds.is_finite() && seconds >= 0.0 => Ok(Duration::from_secs_f64(seconds)),
+        _ => Err(format!("{} needs a number of seconds, not {}", option, value)),
+    }
+}
+
+// The script comes from -e if given, otherwise from the first positional argument
+fn take_source(options: &mut Options) -> Result<Source, String> {
+    if let Some(code) = options.eval.take() {
+        return Ok(Source::Eval(code));
+    }
+
+    if options.positional.is_empty() {
+        return Err("No script given".to_string());
+    }
+
+    match options.positional.remove(0).as_str() {
+        "-" => Ok(Source::Stdin),
+        path => Ok(Source::File(path.to_string())),
+    }
+}
+
+fn legacy_flag(args: Vec<String>) -> Result<Command, String> {
+    let path = match args.as_slice() {
+        [_, path] => path.clone(),
+        _ => return Err(format!("{} needs exactly one script", args[0])),
+    };
+
+    let format = match args[0].as_str() {
+        "--tokens" => return Ok(Command::Tokens { source: Source::File(path), json: false }),
+        "--tokens=json" => return Ok(Command::Tokens { source: Source::File(path), json: true }),
+        "--dump-ast" => AstFormat::Lisp,
+        "--dump-ast=source" => AstFormat::Source,
+        "--dump-cst" => AstFormat::Cst,
+        "--emit=ast-json" => AstFormat::Json,
+        _ => return Ok(Command::RunAstJson { path, args: vec![], options: RunOptions::default() }),
+    };
+
+    Ok(Command::Ast { source: Source::File(path), format })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command, String> {
+        let args: Vec<String> = std::iter::once("rlox").chain(args.iter().copied()).map(String::from).collect();
+        parse_args(&args)
+    }
+
+    fn run(source: Source, args: &[&str], options: RunOptions) -> Command {
+        Command::Run { source, args: args.iter().map(|arg| arg.to_string()).collect(), options }
+    }
+
+    fn file(path: &str) -> Source {
+        Source::File(path.to_string())
+    }
+
+    fn eval(code: &str) -> Source {
+        Source::Eval(code.to_string())
+    }
+
+    fn run_ast_json(path: &str, args: &[&str]) -> Command {
+        let args = args.iter().map(|arg| arg.to_string()).collect();
+        Command::RunAstJson { path: path.to_string(), args, options: RunOptions::default() }
+    }
+
+    #[test]
+    fn parses_commands() {
+        let steps = || RunOptions { max_steps: Some(10), ..RunOptions::default() };
+        let cases: Vec<(&[&str], Command)> = vec![
+            (&[], Command::Repl),
+            (&["repl"], Command::Repl),
+            (&["a.lox"], run(file("a.lox"), &[], RunOptions::default())),
+            (&["run", "a.lox", "x", "-y"], run(file("a.lox"), &["x", "-y"], RunOptions::default())),
+            (&["run", "--max-steps", "10", "a.lox"], run(file("a.lox"), &[], steps())),
+            (&["--max-steps=10", "a.lox"], run(file("a.lox"), &[], steps())),
+            (&["run", "a.lox", "--max-steps", "10"], run(file("a.lox"), &["--max-steps", "10"], RunOptions::default())),
+            (&["check", "a.lox"], Command::Check { source: file("a.lox") }),
+            (&["fmt", "-"], Command::Fmt { source: Source::Stdin }),
+            (&["test", "a", "b"], Command::Test { paths: vec!["a".to_string(), "b".to_string()] }),
+            (&["tokens", "--json", "a.lox"], Command::Tokens { source: file("a.lox"), json: true }),
+            (&["ast", "a.lox"], Command::Ast { source: file("a.lox"), format: AstFormat::Lisp }),
+            (&["ast", "--source", "a.lox"], Command::Ast { source: file("a.lox"), format: AstFormat::Source }),
+            (&["ast", "--cst", "a.lox"], Command::Ast { source: file("a.lox"), format: AstFormat::Cst }),
+            (&["ast", "--json", "a.lox"], Command::Ast { source: file("a.lox"), format: AstFormat::Json }),
+            (&["run", "--ast-json", "a.json", "x"], run_ast_json("a.json", &["x"])),
+            (&["-h"], Command::Help),
+            (&["run", "--help"], Command::Help),
+            (&["--version"], Command::Version),
+        ];
+
+        for (args, expected) in cases {
+            assert_eq!(parse(args), Ok(expected), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn parses_eval_and_stdin() {
+        let cases: Vec<(&[&str], Command)> = vec![
+            (&["-e", "print 1;"], run(eval("print 1;"), &[], RunOptions::default())),
+            (&["run", "--eval=print 1;", "x"], run(eval("print 1;"), &["x"], RunOptions::default())),
+            // After -e everything belongs to the script, even what looks like an option
+            (&["-e", "print 1;", "--max-steps", "5"], run(eval("print 1;"), &["--max-steps", "5"], RunOptions::default())),
+            (&["check", "-e", "print 1;"], Command::Check { source: eval("print 1;") }),
+            (&["-"], run(Source::Stdin, &[], RunOptions::default())),
+            (&["run", "-", "x"], run(Source::Stdin, &["x"], RunOptions::default())),
+            (&["run", "a.lox", "--", "x"], run(file("a.lox"), &["--", "x"], RunOptions::default())),
+        ];
+
+        for (args, expected) in cases {
+            assert_eq!(parse(args), Ok(expected), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn parses_permissions() {
+        let cases: Vec<(&[&str], Permissions)> = vec![
+            (&["--allow-read"], Permissions::default().allow_read_all()),
+            (&["--allow-read=a,b"], Permissions::default().allow_read("a").allow_read("b")),
+            (&["--allow-write=a"], Permissions::default().allow_write("a")),
+            (&["--allow-env", "--allow-run"], Permissions::default().allow_env().allow_spawn()),
+            (&["--allow-all"], Permissions::all()),
+            (&["--deny-clock"], Permissions::default().deny_clock()),
+        ];
+
+        for (flags, permissions) in cases {
+            let args: Vec<&str> = flags.iter().copied().chain(["a.lox"]).collect();
+            let options = RunOptions { permissions, ..RunOptions::default() };
+            assert_eq!(parse(&args), Ok(run(file("a.lox"), &[], options)), "{:?}", flags);
+        }
+    }
+
+    #[test]
+    fn parses_legacy_flags() {
+        let cases: Vec<(&[&str], Command)> = vec![
+            (&["--tokens", "a.lox"], Command::Tokens { source: file("a.lox"), json: false }),
+            (&["--tokens=json", "a.lox"], Command::Tokens { source: file("a.lox"), json: true }),
+            (&["--dump-ast", "a.lox"], Command::Ast { source: file("a.lox"), format: AstFormat::Lisp }),
+            (&["--dump-ast=source", "a.lox"], Command::Ast { source: file("a.lox"), format: AstFormat::Source }),
+            (&["--dump-cst", "a.lox"], Command::Ast { source: file("a.lox"), format: AstFormat::Cst }),
+            (&["--emit=ast-json", "a.lox"], Command::Ast { source: file("a.lox"), format: AstFormat::Json }),
+            (&["--load=ast-json", "a.json"], run_ast_json("a.json", &[])),
+        ];
+
+        for (args, expected) in cases {
+            assert_eq!(parse(args), Ok(expected), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        let cases: Vec<(&[&str], &str)> = vec![
+            (&["--frobnicate", "a.lox"], "Unknown option --frobnicate"),
+            (&["run", "-x"], "Unknown option -x"),
+            (&["run"], "No script given"),
+            (&["check"], "No script given"),
+            (&["test"], "test needs at least one script or directory"),
+            (&["run", "--ast-json"], "--ast-json needs a file"),
+            (&["-e"], "-e needs an argument"),
+            (&["run", "--max-steps"], "--max-steps needs an argument"),
+            (&["--timeout"], "--timeout needs an argument"),
+            (&["--max-steps", "ten", "a.lox"], "--max-steps needs a whole number, not ten"),
+            (&["--max-allocation=-1", "a.lox"], "--max-allocation needs a whole number, not -1"),
+            (&["--timeout", "-1", "a.lox"], "--timeout needs a number of seconds, not -1"),
+            (&["--allow-read=", "a.lox"], "--allow-read needs a comma separated list of paths, not \"\""),
+            (&["--allow-write=a,", "a.lox"], "--allow-write needs a comma separated list of paths, not \"a,\""),
+            (&["--dump-ast"], "--dump-ast needs exactly one script"),
+            (&["--tokens", "a.lox", "b.lox"], "--tokens needs exactly one script"),
+        ];
+
+        for (args, message) in cases {
+            assert_eq!(parse(args), Err(message.to_string()), "{:?}", args);
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use crate::scanner::Token;
+use std::fmt;
+
+// Concrete syntax tree. Unlike the expression tree it keeps every token, including punctuation
+// and the trivia attached to it, so printing a tree gives back the exact source it was parsed from.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SyntaxKind {
+    Root,
+
+    // Declarations and statements
+    ClassDecl,
+    FunDecl,
+    VarDecl,
+    Block,
+    ExprStmt,
+    ForStmt,
+    IfStmt,
+    PrintStmt,
+    ReturnStmt,
+    ThrowStmt,
+    TryStmt,
+    WhileStmt,
+
+    // Expressions
+    Assign,
+    Binary,
+    Call,
+    Get,
+    Grouping,
+    Literal,
+    Logical,
+    Set,
+    Super,
+    This,
+    Unary,
+    Variable,
+}
+
+#[derive(Clone, Debug)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(Token),
+}
+
+#[derive(Clone, Debug)]
+pub struct SyntaxNode {
+    kind: SyntaxKind,
+    children: Vec<SyntaxElement>,
+}
+
+impl SyntaxNode {
+    pub fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
+    pub fn children(&self) -> &[SyntaxElement] {
+        &self.children
+    }
+
+    // Indented outline of the tree, one node or token per line
+    pub fn dump(&self) -> String {
+        let mut out = String::new();
+        self.dump_into(&mut out, 0);
+        out
+    }
+
+    fn dump_into(&self, out: &mut String, depth: usize) {
+        out.push_str(&format!("{}{:?}\n", "  ".repeat(depth), self.kind));
+
+        for child in &self.children {
+            match child {
+                SyntaxElement::Node(node) => node.dump_into(out, depth + 1),
+                SyntaxElement::Token(token) => {
+                    for trivia in token.leading_trivia() {
+                        out.push_str(&format!("{}{:?} {:?}\n", "  ".repeat(depth + 1), trivia.kind(), trivia.text()));
+                    }
+                    out.push_str(&format!("{}{:?} {:?}\n", "  ".repeat(depth + 1), token.kind(), token.lexeme()));
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for child in &self.children {
+            match child {
+                SyntaxElement::Node(node) => write!(f, "{}", node)?,
+                SyntaxElement::Token(token) => {
+                    for trivia in token.leading_trivia() {
+                        write!(f, "{}", trivia.text())?;
+                    }
+                    write!(f, "{}", token.lexeme())?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Collects tokens as the parser consumes them. Nodes are created by wrapping everything pushed
+// since a checkpoint, which lets left-recursive rules like binary expressions decide on their node
+// only after the left operand has already been parsed.
+pub(crate) struct CstBuilder {
+    children: Vec<SyntaxElement>,
+}
+
+pub(crate) type Checkpoint = usize;
+
+impl CstBuilder {
+    pub fn new() -> CstBuilder {
+        CstBuilder { children: vec![] }
+    }
+
+    pub fn token(&mut self, token: Token) {
+        self.children.push(SyntaxElement::Token(token));
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        self.children.len()
+    }
+
+    pub fn wrap(&mut self, checkpoint: Checkpoint, kind: SyntaxKind) {
+        let children = self.children.split_off(checkpoint);
+        self.children.push(SyntaxElement::Node(SyntaxNode { kind, children }));
+    }
+
+    pub fn finish(self) -> SyntaxNode {
+        SyntaxNode {
+            kind: SyntaxKind::Root,
+            children: self.children,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::Parser;
+    use crate::scanner::{Scanner, TriviaKind};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    // The source as the lossless scanner sees it, from the trivia and lexemes of its tokens
+    fn rescan(source: &str) -> String {
+        let mut out = String::new();
+        for token in Scanner::new_lossless(source.to_string()).flatten() {
+            for trivia in token.leading_trivia() {
+                out.push_str(trivia.text());
+            }
+            out.push_str(token.lexeme());
+        }
+        out
+    }
+
+    fn reparse(source: &str) -> (String, bool) {
+        let mut parser = Parser::new(Scanner::new_lossless(source.to_string()));
+        parser.set_diagnostics(Rc::new(RefCell::new(Vec::new())));
+        let tree = parser.parse_cst();
+        (tree.to_string(), parser.had_error())
+    }
+
+    #[test]
+    fn valid_source_round_trips() {
+        let sources = [
+            "",
+            "print 1;",
+            "// just a comment",
+            "var a = 1; // trailing comment\n// own line\n\n\nprint a;\n",
+            "\n\n\tfun f(a,\tb) {\n\t\treturn a  +  b ;\n\t}\n\n",
+            "class A < B {\r\n  init() { this.x = \"a\r\nb\"; }\r\n}\r\n",
+            "if (a) print 1; else {\n  // nothing\n}\n  \t ",
+        ];
+
+        for source in sources {
+            assert_eq!(rescan(source), source);
+            assert_eq!(reparse(source), (source.to_string(), false));
+        }
+    }
+
+    #[test]
+    fn source_with_scan_errors_round_trips() {
+        let sources = [
+            "print 1 @ 2;",
+            "var a = #;\n^ print a;\n",
+            "print \"unterminated;\n",
+            "print 1;\r\n\"never closed\r\n// at all",
+        ];
+
+        for source in sources {
+            assert_eq!(rescan(source), source);
+            assert_eq!(reparse(source), (source.to_string(), true));
+        }
+    }
+
+    #[test]
+    fn unknown_characters_and_unterminated_strings_are_unknown_trivia() {
+        let kinds = |source: &str| -> Vec<(TriviaKind, String)> {
+            let tokens = Scanner::new_lossless(source.to_string()).flatten();
+            tokens.flat_map(|token| token.leading_trivia().to_vec()).map(|t| (t.kind(), t.text().to_string())).collect()
+        };
+
+        assert_eq!(
+            kinds("a @\tb"),
+            vec![
+                (TriviaKind::Whitespace, " ".to_string()),
+                (TriviaKind::Unknown, "@".to_string()),
+                (TriviaKind::Whitespace, "\t".to_string()),
+            ]
+        );
+        assert_eq!(
+            kinds("a // c\r\n\"open"),
+            vec![
+                (TriviaKind::Whitespace, " ".to_string()),
+                (TriviaKind::Comment, "// c".to_string()),
+                (TriviaKind::Whitespace, "\r\n".to_string()),
+                (TriviaKind::Unknown, "\"open".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,59 @@
+use crate::interpreter::RuntimeError;
+use crate::lox_object::LoxObject;
+use crate::scanner::Token;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// One scope of variables. Blocks, calls and methods each get a new scope enclosing the one they
+// were created in, and names are looked up outwards to the globals.
+#[derive(Debug, Default)]
+pub struct Environment {
+    values: HashMap<String, LoxObject>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+
+    pub fn new() -> Environment {
+        Environment::default()
+    }
+
+    pub fn with_enclosing(enclosing: Rc<RefCell<Environment>>) -> Environment {
+        Environment { values: HashMap::new(), enclosing: Some(enclosing) }
+    }
+
+    // Redefining a name in the same scope replaces it, which the REPL relies on
+    pub fn define(&mut self, name: &str, value: LoxObject) {
+        self.values.insert(name.to_string(), value);
+    }
+
+    pub fn get(&self, name: &Token) -> Result<LoxObject, RuntimeError> {
+        self.lookup(name.lexeme())
+            .ok_or_else(|| RuntimeError::new(name, format!("Undefined variable '{}'.", name.lexeme())))
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<LoxObject> {
+        match self.values.get(name) {
+            Some(value) => Some(value.clone()),
+            None => self.enclosing.as_ref().and_then(|enclosing| enclosing.borrow().lookup(name)),
+        }
+    }
+
+    // The variables of this scope only
+    pub fn values(&self) -> Vec<(String, LoxObject)> {
+        self.values.iter().map(|(name, value)| (name.clone(), value.clone())).collect()
+    }
+
+    pub fn assign(&mut self, name: &Token, value: LoxObject) -> Result<(), RuntimeError> {
+        if let Some(slot) = self.values.get_mut(name.lexeme()) {
+            *slot = value;
+            return Ok(());
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+            None => Err(RuntimeError::new(name, format!("Undefined variable '{}'.", name.lexeme()))),
+        }
+    }
+}
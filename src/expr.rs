@@ -1,153 +1,293 @@
-use crate::scanner::{Token};
-use crate::lox_object::LoxObject;
-use std::rc::Rc;
+use crate::scanner::{Span, Token};
+
+// Expressions are a closed set of node types. Anything that walks the tree implements
+// ExpressionProcessor, choosing its own result type, and hands itself to Expr::attach.
+#[derive(Clone, Debug)]
+pub enum Expr {
+    Assign(AssignExpr),
+    Binary(BinaryExpr),
+    Call(CallExpr),
+    Get(GetExpr),
+    Grouping(GroupingExpr),
+    Literal(LiteralExpr),
+    Logical(LogicalExpr),
+    Set(SetExpr),
+    Super(SuperExpr),
+    This(ThisExpr),
+    Unary(UnaryExpr),
+    Variable(VariableExpr),
+}
 
-pub(crate) type Expression = Rc<dyn Expr>;
+impl Expr {
+    pub fn attach<R>(&self, expression_processor: &mut dyn ExpressionProcessor<R>) -> R {
+        match self {
+            Expr::Assign(assign_expr) => expression_processor.process_assign_expr(assign_expr),
+            Expr::Binary(binary_expr) => expression_processor.process_binary_expr(binary_expr),
+            Expr::Call(call_expr) => expression_processor.process_call_expr(call_expr),
+            Expr::Get(get_expr) => expression_processor.process_get_expr(get_expr),
+            Expr::Grouping(grouping_expr) => expression_processor.process_grouping_expr(grouping_expr),
+            Expr::Literal(literal_expr) => expression_processor.process_literal_expr(literal_expr),
+            Expr::Logical(logical_expr) => expression_processor.process_logical_expr(logical_expr),
+            Expr::Set(set_expr) => expression_processor.process_set_expr(set_expr),
+            Expr::Super(super_expr) => expression_processor.process_super_expr(super_expr),
+            Expr::This(this_expr) => expression_processor.process_this_expr(this_expr),
+            Expr::Unary(unary_expr) => expression_processor.process_unary_expr(unary_expr),
+            Expr::Variable(variable_expr) => expression_processor.process_variable_expr(variable_expr),
+        }
+    }
 
-pub trait Expr {
-    fn attach(&self, expression_processor: &dyn ExpressionProcessor) -> LoxObject;
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Assign(assign_expr) => assign_expr.span,
+            Expr::Binary(binary_expr) => binary_expr.span,
+            Expr::Call(call_expr) => call_expr.span,
+            Expr::Get(get_expr) => get_expr.span,
+            Expr::Grouping(grouping_expr) => grouping_expr.span,
+            Expr::Literal(literal_expr) => literal_expr.span,
+            Expr::Logical(logical_expr) => logical_expr.span,
+            Expr::Set(set_expr) => set_expr.span,
+            Expr::Super(super_expr) => super_expr.span,
+            Expr::This(this_expr) => this_expr.span,
+            Expr::Unary(unary_expr) => unary_expr.span,
+            Expr::Variable(variable_expr) => variable_expr.span,
+        }
+    }
+
+    // The line of a token in the expression, for reporting
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            Expr::Assign(assign_expr) => Some(assign_expr.name.line()),
+            Expr::Binary(binary_expr) => Some(binary_expr.operator.line()),
+            Expr::Call(call_expr) => Some(call_expr.paren.line()),
+            Expr::Get(get_expr) => Some(get_expr.name.line()),
+            Expr::Grouping(grouping_expr) => grouping_expr.expression.line(),
+            Expr::Literal(literal_expr) => Some(literal_expr.span.start.line),
+            Expr::Logical(logical_expr) => Some(logical_expr.operator.line()),
+            Expr::Set(set_expr) => Some(set_expr.name.line()),
+            Expr::Super(super_expr) => Some(super_expr.keyword.line()),
+            Expr::This(this_expr) => Some(this_expr.keyword.line()),
+            Expr::Unary(unary_expr) => Some(unary_expr.operator.line()),
+            Expr::Variable(variable_expr) => Some(variable_expr.name.line()),
+        }
+    }
 }
 
-// Binary Expressions --------------------------------------------------------------------------
+// Assign Expressions -------------------------------------------------------------------------
 
-pub struct BinaryExpr {
-    pub left: Rc<dyn Expr>,
-    pub operator: Token,
-    pub right: Rc<dyn Expr>,
+#[derive(Clone, Debug)]
+pub struct AssignExpr {
+    pub name: Token,
+    pub value: Box<Expr>,
+    pub span: Span,
 }
 
-impl Expr for BinaryExpr {
-    fn attach(&self, expression_processor:  &dyn ExpressionProcessor) -> LoxObject {
-        return expression_processor.process_binary_expr(&self);
+impl AssignExpr {
+    pub fn new(name: Token, value: Expr, span: Span) -> AssignExpr {
+        AssignExpr {
+            name,
+            value: Box::new(value),
+            span,
+        }
     }
 }
 
+// Binary Expressions --------------------------------------------------------------------------
+
+#[derive(Clone, Debug)]
+pub struct BinaryExpr {
+    pub left: Box<Expr>,
+    pub operator: Token,
+    pub right: Box<Expr>,
+    pub span: Span,
+}
+
 impl BinaryExpr {
-    pub fn new(left_expr: Rc<dyn Expr>, operator: Token, right_expr: Rc<dyn Expr>) -> BinaryExpr {
+    pub fn new(left_expr: Expr, operator: Token, right_expr: Expr, span: Span) -> BinaryExpr {
         BinaryExpr {
-            left: left_expr,
-            operator: operator,
-            right: right_expr,
+            left: Box::new(left_expr),
+            operator,
+            right: Box::new(right_expr),
+            span,
         }
     }
 }
 
-// Gruiping Expressions --------------------------------------------------------------------------
+// Call Expressions ---------------------------------------------------------------------------
 
-pub struct GroupingExpr {
-    pub expression: Rc<dyn Expr>,
+#[derive(Clone, Debug)]
+pub struct CallExpr {
+    pub callee: Box<Expr>,
+    // The closing parenthesis, whose line is used to report errors in the call
+    pub paren: Token,
+    pub arguments: Vec<Expr>,
+    pub span: Span,
 }
 
-
-impl GroupingExpr {
-    pub fn new(expression: Rc<dyn Expr>) -> GroupingExpr {
-        GroupingExpr {
-            expression: expression,
+impl CallExpr {
+    pub fn new(callee: Expr, paren: Token, arguments: Vec<Expr>, span: Span) -> CallExpr {
+        CallExpr {
+            callee: Box::new(callee),
+            paren,
+            arguments,
+            span,
         }
     }
 }
 
-//impl Expr for GroupingExpr {}
+// Get Expressions ----------------------------------------------------------------------------
 
-impl Expr for GroupingExpr {
-    fn attach(&self, expression_processor:  &dyn ExpressionProcessor) -> LoxObject {
-        return expression_processor.process_grouping_expr(&self);
-    }
+#[derive(Clone, Debug)]
+pub struct GetExpr {
+    pub object: Box<Expr>,
+    pub name: Token,
+    pub span: Span,
 }
 
-// Literal Expressions --------------------------------------------------------------------------
-
-pub trait LiteralExpr : Expr {}
+impl GetExpr {
+    pub fn new(object: Expr, name: Token, span: Span) -> GetExpr {
+        GetExpr {
+            object: Box::new(object),
+            name,
+            span,
+        }
+    }
+}
 
-// BooleanLiteral Expressions -------------------------------------------------------------------
+// Grouping Expressions -------------------------------------------------------------------------
 
-pub struct BooleanLiteral {
-    pub value:  bool,
+#[derive(Clone, Debug)]
+pub struct GroupingExpr {
+    pub expression: Box<Expr>,
+    pub span: Span,
 }
 
-//impl Expr for BooleanLiteral {}
-
-impl Expr for BooleanLiteral {
-    fn attach(&self, expression_processor:  &dyn ExpressionProcessor) -> LoxObject {
-        LoxObject::Boolean(true)
-        //return expressionProcessor.processLiteralExpr((&self));
+impl GroupingExpr {
+    pub fn new(expression: Expr, span: Span) -> GroupingExpr {
+        GroupingExpr {
+            expression: Box::new(expression),
+            span,
+        }
     }
 }
 
-impl LiteralExpr for BooleanLiteral {}
-
-
-// StringLiteral Expressions -------------------------------------------------------------------
+// Literal Expressions --------------------------------------------------------------------------
 
-pub struct StringLiteral {
-    pub(crate) value: String,
+#[derive(Clone, Debug)]
+pub struct LiteralExpr {
+    pub value: LiteralValue,
+    // Literals the parser makes up, like the missing condition of a for loop, take the span of
+    // the code they stand in for
+    pub span: Span,
 }
 
-//impl Expr for StringLiteral {}
-
-impl Expr for StringLiteral {
-    fn attach(&self, expression_processor:  &dyn ExpressionProcessor) -> LoxObject {
-        return expression_processor.process_string_literal_expr(&self);
-    }
+#[derive(Clone, Debug)]
+pub enum LiteralValue {
+    Boolean(bool),
+    Number(f32),
+    String(String),
+    Nil,
 }
 
-impl LiteralExpr for StringLiteral {}
+// Logical Expressions ------------------------------------------------------------------------
 
-// NumberLiteral Expressions -------------------------------------------------------------------
-
-pub struct NumberLiteral {
-    pub value: f32,
+// `and` and `or`. Kept apart from BinaryExpr because the right operand may never be evaluated.
+#[derive(Clone, Debug)]
+pub struct LogicalExpr {
+    pub left: Box<Expr>,
+    pub operator: Token,
+    pub right: Box<Expr>,
+    pub span: Span,
 }
 
-//impl Expr for NumberLiteral {}
-
-impl Expr for NumberLiteral {
-    fn attach(&self, expresion_processor:  &dyn ExpressionProcessor) -> LoxObject {
-        return expresion_processor.process_number_literal_expr(&self);
+impl LogicalExpr {
+    pub fn new(left_expr: Expr, operator: Token, right_expr: Expr, span: Span) -> LogicalExpr {
+        LogicalExpr {
+            left: Box::new(left_expr),
+            operator,
+            right: Box::new(right_expr),
+            span,
+        }
     }
 }
 
-impl LiteralExpr for NumberLiteral {}
-
-// NilLiteral Expressions -------------------------------------------------------------------
+// Set Expressions ----------------------------------------------------------------------------
 
-pub struct NilLiteral {
+#[derive(Clone, Debug)]
+pub struct SetExpr {
+    pub object: Box<Expr>,
+    pub name: Token,
+    pub value: Box<Expr>,
+    pub span: Span,
+}
 
+impl SetExpr {
+    pub fn new(object: Expr, name: Token, value: Expr, span: Span) -> SetExpr {
+        SetExpr {
+            object: Box::new(object),
+            name,
+            value: Box::new(value),
+            span,
+        }
+    }
 }
 
-//impl Expr for NilLiteral {}
+// Super Expressions --------------------------------------------------------------------------
 
-impl Expr for NilLiteral {
-    fn attach(&self, expression_processor :  &dyn ExpressionProcessor) -> LoxObject {
-        return expression_processor.process_nil_literal_expr(&self);
-    }
+#[derive(Clone, Debug)]
+pub struct SuperExpr {
+    pub keyword: Token,
+    pub method: Token,
+    pub span: Span,
 }
 
-impl LiteralExpr for NilLiteral {}
+// This Expressions ---------------------------------------------------------------------------
 
+#[derive(Clone, Debug)]
+pub struct ThisExpr {
+    pub keyword: Token,
+    pub span: Span,
+}
 
-// Unary Expressions --------------------------------------------------------------------------
+// Unary Expressions ----------------------------------------------------------------------------
 
+#[derive(Clone, Debug)]
 pub struct UnaryExpr {
     pub operator: Token,
-    pub right: Rc<dyn Expr>,
+    pub right: Box<Expr>,
+    pub span: Span,
 }
 
-//impl Expr for UnaryExpr {}
-
-impl Expr for UnaryExpr {
-    fn attach(&self, expression_processor :  &dyn ExpressionProcessor) -> LoxObject {
-        return expression_processor.process_unary_expr(&self);
+impl UnaryExpr {
+    pub fn new(operator: Token, right_expr: Expr, span: Span) -> UnaryExpr {
+        UnaryExpr {
+            operator,
+            right: Box::new(right_expr),
+            span,
+        }
     }
 }
 
+// Variable Expressions -----------------------------------------------------------------------
+
+#[derive(Clone, Debug)]
+pub struct VariableExpr {
+    pub name: Token,
+    pub span: Span,
+}
+
 // ----------------------------------------------------------------------------------------------
 
-pub trait ExpressionProcessor {
-    fn process_unary_expr(&self, unary_expr: &UnaryExpr) -> LoxObject;
-    fn process_binary_expr(&self, binary_expr: &BinaryExpr) -> LoxObject;
-    fn process_grouping_expr(&self, grouping_expr: &GroupingExpr) -> LoxObject;
-    fn process_string_literal_expr(&self, string_literal_expr: &StringLiteral) -> LoxObject;
-    fn process_number_literal_expr(&self, number_literal_expr: &NumberLiteral) -> LoxObject;
-    fn process_nil_literal_expr(&self, nil_literal_expr: &NilLiteral) -> LoxObject;
-}
\ No newline at end of file
+pub trait ExpressionProcessor<R> {
+    fn process_assign_expr(&mut self, assign_expr: &AssignExpr) -> R;
+    fn process_unary_expr(&mut self, unary_expr: &UnaryExpr) -> R;
+    fn process_binary_expr(&mut self, binary_expr: &BinaryExpr) -> R;
+    fn process_grouping_expr(&mut self, grouping_expr: &GroupingExpr) -> R;
+    fn process_literal_expr(&mut self, literal_expr: &LiteralExpr) -> R;
+    fn process_logical_expr(&mut self, logical_expr: &LogicalExpr) -> R;
+    fn process_call_expr(&mut self, call_expr: &CallExpr) -> R;
+    fn process_get_expr(&mut self, get_expr: &GetExpr) -> R;
+    fn process_set_expr(&mut self, set_expr: &SetExpr) -> R;
+    fn process_super_expr(&mut self, super_expr: &SuperExpr) -> R;
+    fn process_this_expr(&mut self, this_expr: &ThisExpr) -> R;
+    fn process_variable_expr(&mut self, variable_expr: &VariableExpr) -> R;
+}
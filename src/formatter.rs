@@ -0,0 +1,152 @@
+use crate::cst::{SyntaxElement, SyntaxKind, SyntaxNode};
+use crate::scanner::{Token, TokenKind, TriviaKind};
+
+const INDENT: &str = "    ";
+
+// Rewrites source with normalized spacing and layout: one statement per line, block contents
+// indented, a single blank line kept wherever the source had one or more. Works from the concrete
+// syntax tree so that comments survive: a comment that began its own line stays on its own line,
+// any other comment trails the token before it.
+pub fn format(root: &SyntaxNode) -> String {
+    let mut formatter = Formatter {
+        out: String::new(),
+        previous: None,
+        depth: 0,
+        paren_depth: 0,
+        line_break: false,
+        blank_line: false,
+    };
+
+    formatter.node(root);
+
+    let mut out = formatter.out.trim_end().to_string();
+    out.push('\n');
+    out
+}
+
+struct Formatter {
+    out: String,
+    previous: Option<(TokenKind, SyntaxKind)>,
+    // Open braces and parentheses around the current token
+    depth: usize,
+    paren_depth: usize,
+    // Set when the next token should start a new line, and whether a blank line goes before it
+    line_break: bool,
+    blank_line: bool,
+}
+
+impl Formatter {
+    fn node(&mut self, node: &SyntaxNode) {
+        for child in node.children() {
+            match child {
+                SyntaxElement::Node(child) => self.node(child),
+                SyntaxElement::Token(token) => self.token(token, node.kind()),
+            }
+        }
+    }
+
+    fn token(&mut self, token: &Token, parent: SyntaxKind) {
+        let mut newline_before = self.out.is_empty();
+
+        for trivia in token.leading_trivia() {
+            match trivia.kind() {
+                TriviaKind::Whitespace => {
+                    let newlines = trivia.text().matches('\n').count();
+                    newline_before |= newlines > 0;
+                    self.blank_line |= newlines > 1 && !self.out.is_empty();
+                }
+                TriviaKind::Comment => {
+                    if newline_before {
+                        self.start_line();
+                    } else {
+                        self.out.push(' ');
+                    }
+
+                    self.out.push_str(trivia.text());
+                    self.line_break = true;
+                    newline_before = false;
+                }
+                TriviaKind::Unknown => self.out.push_str(trivia.text()),
+            }
+        }
+
+        if token.kind() == TokenKind::Eof {
+            return;
+        }
+
+        let kind = token.kind();
+        if kind == TokenKind::RightBrace {
+            self.depth = self.depth.saturating_sub(1);
+            // An empty block stays as {}
+            self.line_break = self.line_break && !self.follows(TokenKind::LeftBrace);
+        }
+
+        // These stay on the line of a closing brace: } else, });
+        let joins_brace = self.follows(TokenKind::RightBrace)
+            && matches!(kind, TokenKind::Else | TokenKind::Catch | TokenKind::Finally | TokenKind::Semicolon | TokenKind::RightParen | TokenKind::Comma);
+
+        if self.line_break && !joins_brace {
+            self.start_line();
+        } else if self.space_before(kind, parent) {
+            self.out.push(' ');
+        }
+
+        self.out.push_str(token.lexeme());
+        self.previous = Some((kind, parent));
+        self.line_break = false;
+        self.blank_line = false;
+
+        match kind {
+            TokenKind::LeftBrace => {
+                self.depth += 1;
+                self.line_break = true;
+            }
+            TokenKind::RightBrace => self.line_break = true,
+            TokenKind::LeftParen => self.paren_depth += 1,
+            TokenKind::RightParen => self.paren_depth = self.paren_depth.saturating_sub(1),
+            // The semicolons inside a for clause don't end a statement
+            TokenKind::Semicolon => self.line_break = self.paren_depth == 0,
+            _ => {}
+        }
+    }
+
+    fn follows(&self, kind: TokenKind) -> bool {
+        matches!(self.previous, Some((previous, _)) if previous == kind)
+    }
+
+    fn space_before(&self, kind: TokenKind, parent: SyntaxKind) -> bool {
+        if self.at_line_start() {
+            return false;
+        }
+
+        match self.previous {
+            Some((TokenKind::LeftParen, _)) | Some((TokenKind::Dot, _)) => false,
+            Some((TokenKind::LeftBrace, _)) if kind == TokenKind::RightBrace => false,
+            // The operator of a unary expression sticks to its operand
+            Some((_, SyntaxKind::Unary)) => false,
+            // Calls and declarations put the parameter list right after the name
+            Some((TokenKind::Identifier, SyntaxKind::FunDecl)) if kind == TokenKind::LeftParen => false,
+            _ if kind == TokenKind::LeftParen => parent != SyntaxKind::Call,
+            _ => !matches!(kind, TokenKind::RightParen | TokenKind::Semicolon | TokenKind::Comma | TokenKind::Dot),
+        }
+    }
+
+    fn at_line_start(&self) -> bool {
+        let line = self.out.rsplit('\n').next().unwrap_or("");
+        line.chars().all(|c| c == ' ')
+    }
+
+    // Ends the current line and indents the next, with a blank line between if the source had one
+    fn start_line(&mut self) {
+        if !self.out.is_empty() && !self.out.ends_with('\n') {
+            self.out.push('\n');
+        }
+
+        if self.blank_line && !self.out.is_empty() {
+            self.out.push('\n');
+        }
+        self.blank_line = false;
+
+        self.out.push_str(&INDENT.repeat(self.depth));
+    }
+}
@@ -0,0 +1,94 @@
+use crate::lox_class::{HostMembers, LoxClass, LoxInstance, Method};
+use crate::lox_object::LoxObject;
+use std::any::Any;
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+// A Rust type exposed to Lox as a class. Each instance holds its own Rc<RefCell<T>>, made by the
+// constructor, which runs as the class's init method. Lox code can subclass a host class; a
+// subclass with its own init must call super.init(...) before the host methods will work.
+//
+//   let counter = HostClass::new("Counter", 1, |arguments| Ok(Counter { count: f32::try_from(&arguments[0])? }))
+//       .method("increment", 0, |counter, _| {
+//           counter.borrow_mut().count += 1.0;
+//           Ok(LoxObject::Nil)
+//       })
+//       .getter("count", |counter| LoxObject::from(counter.borrow().count));
+//   lox.register_class(counter);
+pub struct HostClass<T> {
+    name: String,
+    members: HostMembers,
+    marker: PhantomData<T>,
+}
+
+impl<T: 'static> HostClass<T> {
+
+    pub fn new<F>(name: &str, arity: usize, constructor: F) -> HostClass<T>
+    where
+        F: Fn(&[LoxObject]) -> Result<T, String> + 'static,
+    {
+        let init = move |instance: &Rc<RefCell<LoxInstance>>, arguments: Vec<LoxObject>| {
+            let data: Rc<dyn Any> = Rc::new(RefCell::new(constructor(&arguments)?));
+            instance.borrow_mut().set_host_data(data);
+            Ok(LoxObject::Instance(instance.clone()))
+        };
+
+        let mut members = HostMembers::default();
+        members.methods.insert("init".to_string(), Method::Host { arity, function: Rc::new(init) });
+
+        HostClass { name: name.to_string(), members, marker: PhantomData }
+    }
+
+    pub fn method<F>(mut self, name: &str, arity: usize, method: F) -> HostClass<T>
+    where
+        F: Fn(&Rc<RefCell<T>>, &[LoxObject]) -> Result<LoxObject, String> + 'static,
+    {
+        let function = move |instance: &Rc<RefCell<LoxInstance>>, arguments: Vec<LoxObject>| {
+            method(&data::<T>(instance)?, &arguments)
+        };
+
+        self.members.methods.insert(name.to_string(), Method::Host { arity, function: Rc::new(function) });
+        self
+    }
+
+    pub fn getter<F>(mut self, name: &str, getter: F) -> HostClass<T>
+    where
+        F: Fn(&Rc<RefCell<T>>) -> LoxObject + 'static,
+    {
+        let getter = move |instance: &Rc<RefCell<LoxInstance>>| Ok(getter(&data::<T>(instance)?));
+
+        self.members.getters.insert(name.to_string(), Rc::new(getter));
+        self
+    }
+
+    // A property with a getter but no setter is read-only
+    pub fn setter<F>(mut self, name: &str, setter: F) -> HostClass<T>
+    where
+        F: Fn(&Rc<RefCell<T>>, LoxObject) -> Result<(), String> + 'static,
+    {
+        let setter = move |instance: &Rc<RefCell<LoxInstance>>, value| setter(&data::<T>(instance)?, value);
+
+        self.members.setters.insert(name.to_string(), Rc::new(setter));
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub(crate) fn into_class(self) -> LoxClass {
+        LoxClass::new_host(self.name, self.members)
+    }
+
+}
+
+// The Rust value behind an instance, which is missing if a subclass initializer skipped super.init
+fn data<T: 'static>(instance: &Rc<RefCell<LoxInstance>>) -> Result<Rc<RefCell<T>>, String> {
+    let instance = instance.borrow();
+
+    instance
+        .host_data()
+        .and_then(|data| data.downcast::<RefCell<T>>().ok())
+        .ok_or_else(|| format!("{} instance has not been initialized.", instance.class().name()))
+}
@@ -1,109 +1,926 @@
-use crate::expr::{Expression, UnaryExpr, ExpressionProcessor, BinaryExpr, GroupingExpr, StringLiteral, NumberLiteral, NilLiteral};
+use crate::environment::Environment;
+use crate::expr::{
+    AssignExpr, BinaryExpr, CallExpr, Expr, ExpressionProcessor, GetExpr, GroupingExpr, LiteralExpr, LiteralValue,
+    LogicalExpr, SetExpr, SuperExpr, ThisExpr, UnaryExpr, VariableExpr,
+};
+use crate::lox_callable::LoxCallable;
+use crate::lox_class::{LoxClass, LoxInstance};
+use crate::lox_function::LoxFunction;
 use crate::lox_object::LoxObject;
-//use crate::scanner::TokenType::{*};
-use crate::scanner::{TokenType};
+use crate::natives;
+use crate::permissions::Permissions;
+use crate::scanner::{Token, TokenKind};
+use crate::stmt::{
+    BlockStmt, ClassStmt, ExpressionStmt, FunctionStmt, IfStmt, PrintStmt, ReturnStmt, Stmt, StatementProcessor,
+    ThrowStmt, TryStmt, VarStmt, WhileStmt,
+};
+use crate::{error, stack_exhausted, stack_position, stderr_sink, stdout_sink, Sink, DEFAULT_STACK_SIZE};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::{fmt, mem};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+pub struct RuntimeError {
+    line: usize,
+    message: String,
+    kind: RuntimeErrorKind,
+    // The calls in progress when the error happened, innermost first. Empty for an error in
+    // top-level code.
+    stack: Vec<Frame>,
+    // What a throw statement threw. Errors the interpreter raises itself have no value until
+    // they are caught.
+    value: Option<LoxObject>,
+}
+
+// A function on the call stack and the line it had reached
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    function: String,
+    file: String,
+    line: usize,
+}
+
+// Lets a host tell a limit it imposed apart from a mistake in the script
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RuntimeErrorKind {
+    Error,
+    BudgetExhausted,
+    AllocationExhausted,
+    Interrupted,
+}
+
+impl RuntimeError {
+
+    pub fn new(token: &Token, message: String) -> RuntimeError {
+        RuntimeError::with_kind(token.line(), message, RuntimeErrorKind::Error)
+    }
+
+    fn with_kind(line: usize, message: String, kind: RuntimeErrorKind) -> RuntimeError {
+        RuntimeError { line, message, kind, stack: vec![], value: None }
+    }
+
+    fn budget_exhausted(line: usize, max_steps: u64) -> RuntimeError {
+        let message = format!("Exceeded the limit of {} steps.", max_steps);
+        RuntimeError::with_kind(line, message, RuntimeErrorKind::BudgetExhausted)
+    }
+
+    fn interrupted(line: usize, message: &str) -> RuntimeError {
+        RuntimeError::with_kind(line, message.to_string(), RuntimeErrorKind::Interrupted)
+    }
+
+    fn stack_overflow(line: usize) -> RuntimeError {
+        RuntimeError::with_kind(line, "Stack overflow.".to_string(), RuntimeErrorKind::Error)
+    }
+
+    fn allocation_exhausted(token: &Token, max_allocation: usize) -> RuntimeError {
+        let message = format!("Exceeded the allocation budget of {} bytes.", max_allocation);
+        RuntimeError::with_kind(token.line(), message, RuntimeErrorKind::AllocationExhausted)
+    }
+
+    pub fn kind(&self) -> RuntimeErrorKind {
+        self.kind
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn stack(&self) -> &[Frame] {
+        &self.stack
+    }
+
+    pub fn value(&self) -> Option<&LoxObject> {
+        self.value.as_ref()
+    }
+
+    // A limit the host imposed stops the program outright. Anything else a script can catch.
+    fn is_catchable(&self) -> bool {
+        matches!(self.kind, RuntimeErrorKind::Error | RuntimeErrorKind::AllocationExhausted)
+    }
+
+}
+
+impl Frame {
+
+    pub fn function(&self) -> &str {
+        &self.function
+    }
+
+    pub fn file(&self) -> &str {
+        &self.file
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+}
+
+impl fmt::Display for Frame {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "at {} ({}:{})", self.function, self.file, self.line)
+    }
+}
+
+// A stack trace longer than this shows only its innermost frames, as a runaway recursion would
+// otherwise bury the error under hundreds of identical lines
+const MAX_FRAMES_SHOWN: usize = 16;
+
+// Writes a stack trace below an error message, one frame to a line
+pub fn write_stack(out: &mut dyn fmt::Write, stack: &[Frame]) -> fmt::Result {
+    for frame in stack.iter().take(MAX_FRAMES_SHOWN) {
+        writeln!(out, "    {}", frame)?;
+    }
+
+    if stack.len() > MAX_FRAMES_SHOWN {
+        writeln!(out, "    ... {} more", stack.len() - MAX_FRAMES_SHOWN)?;
+    }
+
+    Ok(())
+}
+
+// Why execution of a statement stopped early. A return travels up to the enclosing call the same
+// way an error travels up to interpret.
+#[derive(Debug)]
+pub enum Unwind {
+    Error(RuntimeError),
+    Return(LoxObject),
+}
+
+impl From<RuntimeError> for Unwind {
+    fn from(error: RuntimeError) -> Unwind {
+        Unwind::Error(error)
+    }
+}
+
+// A call in progress and the line it was made from
+struct Call {
+    function: String,
+    line: usize,
+}
+
+// Stops a running interpreter from another thread, or from a signal handler. The program stops at
+// the next statement or expression with an Interrupted error.
+#[derive(Clone, Debug, Default)]
+pub struct InterruptHandle {
+    interrupted: Arc<AtomicBool>,
+}
+
+impl InterruptHandle {
+
+    pub fn new() -> InterruptHandle {
+        InterruptHandle::default()
+    }
+
+    pub fn interrupt(&self) {
+        self.interrupted.store(true, Ordering::Relaxed);
+    }
+
+    fn clear(&self) {
+        self.interrupted.store(false, Ordering::Relaxed);
+    }
+
+    fn take(&self) -> bool {
+        self.interrupted.load(Ordering::Relaxed) && self.interrupted.swap(false, Ordering::Relaxed)
+    }
+}
+
+// The clock is only read every so many steps, as reading it costs more than a step
+const STEPS_PER_CLOCK_CHECK: u64 = 1024;
+
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1000;
 
 pub struct Interpreter {
-    // expression: Expression,
+    globals: Rc<RefCell<Environment>>,
+    environment: Rc<RefCell<Environment>>,
+    // Arguments given after the script name on the command line
+    script_args: Vec<String>,
+    start_time: Instant,
+    // Where print writes, and where runtime errors are reported
+    output: Sink,
+    diagnostics: Sink,
+    // Every statement executed and expression evaluated is a step
+    steps: u64,
+    max_steps: Option<u64>,
+    // The line of the statement being executed
+    line: usize,
+    // The calls in progress, outermost first
+    calls: Vec<Call>,
+    max_call_depth: usize,
+    // How many statements, expressions and calls are being run inside one another, and where the
+    // Rust stack was when the outermost one began. Each level recurses on the stack, so nesting
+    // is stopped before it would use up the stack the interpreter was given.
+    nesting: usize,
+    stack_start: usize,
+    stack_size: usize,
+    // The name of the file being run, for stack traces
+    file: String,
+    // Bytes allocated for strings, lists and instances the program has created. Nothing is taken
+    // off when a value is dropped, so this is a running total rather than what is live.
+    allocated: usize,
+    max_allocation: Option<usize>,
+    interrupt: InterruptHandle,
+    timeout: Option<Duration>,
+    deadline: Option<Instant>,
+    permissions: Permissions,
+    // The class of caught runtime errors, kept here as a script may shadow the global
+    error_class: Rc<LoxClass>,
 }
 
 impl Interpreter {
 
     pub fn new() -> Interpreter {
-        Interpreter {  }
+        let mut globals = Environment::new();
+        natives::define_globals(&mut globals);
+        let error_class = natives::error_class();
+        globals.define("Error", LoxObject::Class(error_class.clone()));
+
+        let globals = Rc::new(RefCell::new(globals));
+        Interpreter {
+            globals: globals.clone(),
+            environment: globals,
+            script_args: vec![],
+            start_time: Instant::now(),
+            output: stdout_sink(),
+            diagnostics: stderr_sink(),
+            steps: 0,
+            max_steps: None,
+            line: 0,
+            calls: vec![],
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            nesting: 0,
+            stack_start: 0,
+            stack_size: DEFAULT_STACK_SIZE,
+            file: "<script>".to_string(),
+            allocated: 0,
+            max_allocation: None,
+            interrupt: InterruptHandle::new(),
+            timeout: None,
+            deadline: None,
+            permissions: Permissions::new(),
+            error_class,
+        }
     }
 
-    pub fn interpret(&self, expresion : Expression) {
-        let object = self.evaluate(expresion);
+    pub fn set_output(&mut self, output: Sink) {
+        self.output = output;
+    }
 
-        println!("{:?}", object)
+    pub fn set_diagnostics(&mut self, diagnostics: Sink) {
+        self.diagnostics = diagnostics;
     }
 
-    fn evaluate(&self, expression : Expression)  -> LoxObject {
-        return expression.attach(self);
+    pub fn diagnostics(&self) -> Sink {
+        self.diagnostics.clone()
     }
 
-}
+    // Every global variable, function and class, sorted by name
+    pub fn globals(&self) -> Vec<(String, LoxObject)> {
+        let mut globals = self.globals.borrow().values();
+        globals.sort_by(|(left, _), (right, _)| left.cmp(right));
+        globals
+    }
 
-impl ExpressionProcessor for Interpreter {
+    pub fn set_script_args(&mut self, args: Vec<String>) {
+        self.script_args = args;
+    }
 
-    fn process_unary_expr(&self, unary_expr: &UnaryExpr) -> LoxObject {
-        let object = self.evaluate(unary_expr.right.clone());
+    pub fn script_args(&self) -> &[String] {
+        &self.script_args
+    }
+
+    pub fn start_time(&self) -> Instant {
+        self.start_time
+    }
 
-        match unary_expr.operator.token_type() {
+    // Stops the program with a BudgetExhausted error once it has taken this many steps. None,
+    // the default, is no limit.
+    pub fn set_max_steps(&mut self, max_steps: Option<u64>) {
+        self.max_steps = max_steps;
+    }
 
-            TokenType::Minus => return LoxObject::Number(-object.to_number()),
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
 
-            TokenType::Bang => return LoxObject::Boolean(!object.is_truthy()),
+    // What stack traces call the source being run, usually its path
+    pub fn set_file(&mut self, file: &str) {
+        self.file = file.to_string();
+    }
 
-            _ => panic!("processUnaryExpr")
+    // How deeply calls may nest before the program stops with a stack overflow
+    pub fn set_max_call_depth(&mut self, max_call_depth: usize) {
+        self.max_call_depth = max_call_depth;
+    }
+
+    // How much stack the thread running the program has. Deep nesting stops with a stack overflow
+    // well before it is used up.
+    pub fn set_stack_size(&mut self, stack_size: usize) {
+        self.stack_size = stack_size;
+    }
+
+    // An allocation budget: stops the program with an AllocationExhausted error once it has
+    // allocated this many bytes in all, however much of that has since been freed. None, the
+    // default, is no limit.
+    pub fn set_max_allocation(&mut self, max_allocation: Option<usize>) {
+        self.max_allocation = max_allocation;
+    }
+
+    pub fn allocated(&self) -> usize {
+        self.allocated
+    }
+
+    // What the program may do outside the interpreter, checked by native functions
+    pub fn set_permissions(&mut self, permissions: Permissions) {
+        self.permissions = permissions;
+    }
+
+    pub fn permissions(&self) -> &Permissions {
+        &self.permissions
+    }
+
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.interrupt.clone()
+    }
+
+    // Lets a handle made before this interpreter, e.g. one a signal handler holds, interrupt it
+    pub fn set_interrupt_handle(&mut self, interrupt: InterruptHandle) {
+        self.interrupt = interrupt;
+    }
+
+    // Stops the program with an Interrupted error once it has run for this long, counted from
+    // now and again from each reset_usage
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+        self.deadline = timeout.map(|timeout| Instant::now() + timeout);
+    }
+
+    // Starts counting steps, allocation and time against their limits from zero again, and forgets
+    // the last line run and any interrupt that came while nothing was running
+    pub fn reset_usage(&mut self) {
+        self.steps = 0;
+        self.line = 0;
+        self.allocated = 0;
+        self.deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        self.interrupt.clear();
+    }
+
+    // Accounts for memory the program is about to allocate, failing if that would go over the
+    // allocation budget
+    pub fn allocate(&mut self, bytes: usize, token: &Token) -> Result<(), RuntimeError> {
+        let allocated = self.allocated.saturating_add(bytes);
+        match self.max_allocation {
+            Some(max_allocation) if allocated > max_allocation => {
+                Err(RuntimeError::allocation_exhausted(token, max_allocation))
+            }
+            _ => {
+                self.allocated = allocated;
+                Ok(())
+            }
         }
+    }
 
+    // Returns false if a runtime error, which is reported, stopped the program. State defined
+    // before the error is kept, so the REPL can carry on.
+    pub fn interpret(&mut self, statements: &[Stmt]) -> bool {
+        match self.execute_statements(statements) {
+            Ok(()) => true,
+            Err(runtime_error) => {
+                self.report(&runtime_error);
+                false
+            }
+        }
+    }
 
+    // Evaluates an expression typed at the REPL, reporting any runtime error
+    pub fn interpret_expression(&mut self, expression: &Expr) -> Option<LoxObject> {
+        match self.evaluate_expression(expression) {
+            Ok(value) => Some(value),
+            Err(runtime_error) => {
+                self.report(&runtime_error);
+                None
+            }
+        }
     }
 
-    fn process_binary_expr(&self, binary_expr: &BinaryExpr) -> LoxObject {
-        let left = self.evaluate(binary_expr.left.clone());
-        let right = self.evaluate(binary_expr.right.clone());
+    // As interpret, but leaves the error to the caller
+    pub fn execute_statements(&mut self, statements: &[Stmt]) -> Result<(), RuntimeError> {
+        for statement in statements {
+            match self.execute(statement) {
+                Ok(()) => {}
+                Err(Unwind::Error(runtime_error)) => return Err(runtime_error),
+                // The parser only accepts return inside a function body
+                Err(Unwind::Return(_)) => return Ok(()),
+            }
+        }
 
-        //let x = binaryExpr.operator.token_type();
-        match binary_expr.operator.token_type() {
+        Ok(())
+    }
 
-            // Arithmetic binary operations
-            TokenType::Minus => return LoxObject::Number(left.to_number() - right.to_number()),
+    pub fn evaluate_expression(&mut self, expression: &Expr) -> Result<LoxObject, RuntimeError> {
+        self.evaluate(expression)
+    }
 
-            // TODO handle String type
-            TokenType::Plus => {
+    pub fn global(&self, name: &str) -> Option<LoxObject> {
+        self.globals.borrow().lookup(name)
+    }
 
-                if let LoxObject::Number(left_num) = left {
-                    if let LoxObject::Number(right_num) = right {
-                        return LoxObject::Number(left_num + right_num);
-                    }
-                    todo!() // syntax error
+    pub fn define_global(&mut self, name: &str, value: LoxObject) {
+        self.globals.borrow_mut().define(name, value);
+    }
+
+    fn report(&self, runtime_error: &RuntimeError) {
+        error(&self.diagnostics, runtime_error.line, &runtime_error.message);
+
+        let mut trace = String::new();
+        let _ = write_stack(&mut trace, &runtime_error.stack);
+        let _ = write!(self.diagnostics.borrow_mut(), "{}", trace);
+    }
+
+    fn execute(&mut self, statement: &Stmt) -> Result<(), Unwind> {
+        if let Some(line) = statement.line() {
+            self.line = line;
+        }
+
+        self.step()?;
+        self.deeper(self.line)?;
+        let result = statement.attach(self);
+        self.nesting -= 1;
+        result
+    }
+
+    // Runs the statements in the given scope, restoring the current scope however they finish
+    pub fn execute_block(&mut self, statements: &[Stmt], environment: Environment) -> Result<(), Unwind> {
+        let previous = mem::replace(&mut self.environment, Rc::new(RefCell::new(environment)));
+
+        let result = statements.iter().try_for_each(|statement| self.execute(statement));
+
+        self.environment = previous;
+        result
+    }
+
+    fn evaluate(&mut self, expression : &Expr) -> Result<LoxObject, RuntimeError> {
+        self.step()?;
+        self.deeper(expression.line().unwrap_or(self.line))?;
+        let result = expression.attach(self);
+        self.nesting -= 1;
+        result
+    }
+
+    // Goes one level deeper, failing before the Rust stack could overflow. The caller comes
+    // back up once it is done.
+    fn deeper(&mut self, line: usize) -> Result<(), RuntimeError> {
+        if self.nesting == 0 {
+            self.stack_start = stack_position();
+        } else if stack_exhausted(self.stack_start, self.stack_size) {
+            return Err(RuntimeError::stack_overflow(line));
+        }
+
+        self.nesting += 1;
+        Ok(())
+    }
+
+    // Each step is a safe point at which the program can be stopped
+    fn step(&mut self) -> Result<(), RuntimeError> {
+        if let Some(max_steps) = self.max_steps {
+            if self.steps >= max_steps {
+                return Err(RuntimeError::budget_exhausted(self.line, max_steps));
+            }
+        }
+
+        if self.interrupt.take() {
+            return Err(RuntimeError::interrupted(self.line, "Interrupted."));
+        }
+
+        if let Some(deadline) = self.deadline {
+            if self.steps.is_multiple_of(STEPS_PER_CLOCK_CHECK) && Instant::now() >= deadline {
+                return Err(RuntimeError::interrupted(self.line, "Timed out."));
+            }
+        }
+
+        self.steps += 1;
+        Ok(())
+    }
+
+    fn number_operand(operator: &Token, operand: &LoxObject) -> Result<f32, RuntimeError> {
+        match operand {
+            LoxObject::Number(value) => Ok(*value),
+            _ => Err(RuntimeError::new(operator, "Operand must be a number.".to_string())),
+        }
+    }
+
+    fn number_operands(operator: &Token, left: &LoxObject, right: &LoxObject) -> Result<(f32, f32), RuntimeError> {
+        match (left, right) {
+            (LoxObject::Number(left), LoxObject::Number(right)) => Ok((*left, *right)),
+            _ => Err(RuntimeError::new(operator, "Operands must be numbers.".to_string())),
+        }
+    }
+
+    fn call(&mut self, callee: LoxObject, arguments: Vec<LoxObject>, paren: &Token) -> Result<LoxObject, RuntimeError> {
+        let (callable, name): (&dyn LoxCallable, &str) = match &callee {
+            LoxObject::Function(function) => (function.as_ref(), function.name()),
+            LoxObject::Native(function) => (function.as_ref(), function.name()),
+            LoxObject::Class(class) => (class, class.name()),
+            _ => return Err(RuntimeError::new(paren, "Can only call functions and classes.".to_string())),
+        };
+
+        if arguments.len() != callable.arity() {
+            let message = format!("Expected {} arguments but got {}.", callable.arity(), arguments.len());
+            return Err(RuntimeError::new(paren, message));
+        }
+
+        if self.calls.len() >= self.max_call_depth {
+            return Err(RuntimeError::stack_overflow(paren.line()));
+        }
+
+        self.deeper(paren.line())?;
+        self.calls.push(Call { function: name.to_string(), line: paren.line() });
+        let result = callable.call(self, arguments, paren).map_err(|error| self.trace(error));
+        self.calls.pop();
+        self.nesting -= 1;
+
+        // Strings and lists made by native functions count against the allocation budget
+        if let (LoxObject::Native(_), Ok(value)) = (&callee, &result) {
+            self.allocate(Interpreter::size_of(value), paren)?;
+        }
+        result
+    }
+
+    // Records the calls in progress in an error leaving the innermost of them. Each call is shown
+    // at the line it had reached: the line of the error for the innermost, otherwise the line of
+    // the call it made.
+    fn trace(&self, mut error: RuntimeError) -> RuntimeError {
+        if !error.stack.is_empty() {
+            return error;
+        }
+
+        let mut line = error.line;
+        for call in self.calls.iter().rev() {
+            error.stack.push(Frame { function: call.function.clone(), file: self.file.clone(), line });
+            line = call.line;
+        }
+        error.stack.push(Frame { function: "script".to_string(), file: self.file.clone(), line });
+
+        error
+    }
+
+    // The value is an instance of Error or of a subclass of it
+    fn error_instance<'a>(&self, value: &'a LoxObject) -> Option<&'a Rc<RefCell<LoxInstance>>> {
+        match value {
+            LoxObject::Instance(instance) if instance.borrow().class().is_subclass_of(&self.error_class) => Some(instance),
+            _ => None,
+        }
+    }
+
+    // What a catch clause binds for a caught error: the value that was thrown, or an Error for an
+    // error the interpreter raised. Either way an Error has its stack trace by then.
+    fn error_value(&self, error: RuntimeError) -> LoxObject {
+        let stack: Vec<LoxObject> = error.stack.iter().map(|frame| LoxObject::String(frame.to_string())).collect();
+        let stack = LoxObject::List(Rc::new(stack));
+
+        if let Some(value) = error.value {
+            if let Some(instance) = self.error_instance(&value) {
+                if instance.borrow().field("stack").is_none() {
+                    instance.borrow_mut().set_field("stack", stack);
                 }
+            }
+            return value;
+        }
 
-                if let LoxObject::String(left_str) = left {
-                    if let LoxObject::String(right_str) = right {
-                        return LoxObject::String(format!("{}{}", left_str, right_str));
-                    }
-                    todo!() // syntax error
+        let mut instance = LoxInstance::new(self.error_class.clone());
+        instance.set_field("message", LoxObject::String(error.message));
+        instance.set_field("line", LoxObject::Number(error.line as f32));
+        instance.set_field("stack", stack);
+        LoxObject::Instance(Rc::new(RefCell::new(instance)))
+    }
+
+    fn size_of(value: &LoxObject) -> usize {
+        mem::size_of::<LoxObject>()
+            + match value {
+                LoxObject::String(value) => value.len(),
+                LoxObject::List(elements) => elements.len() * mem::size_of::<LoxObject>(),
+                _ => 0,
+            }
+    }
+
+}
+
+impl Default for Interpreter {
+
+    fn default() -> Interpreter {
+        Interpreter::new()
+    }
+}
+
+impl ExpressionProcessor<Result<LoxObject, RuntimeError>> for Interpreter {
+
+    fn process_assign_expr(&mut self, assign_expr: &AssignExpr) -> Result<LoxObject, RuntimeError> {
+        let value = self.evaluate(&assign_expr.value)?;
+        self.environment.borrow_mut().assign(&assign_expr.name, value.clone())?;
+        Ok(value)
+    }
+
+    fn process_unary_expr(&mut self, unary_expr: &UnaryExpr) -> Result<LoxObject, RuntimeError> {
+        let object = self.evaluate(&unary_expr.right)?;
+
+        match unary_expr.operator.kind() {
+
+            TokenKind::Minus => Ok(LoxObject::Number(-Interpreter::number_operand(&unary_expr.operator, &object)?)),
+
+            TokenKind::Bang => Ok(LoxObject::Boolean(!object.is_truthy())),
+
+            _ => panic!("processUnaryExpr")
+        }
+    }
+
+    fn process_binary_expr(&mut self, binary_expr: &BinaryExpr) -> Result<LoxObject, RuntimeError> {
+        let left = self.evaluate(&binary_expr.left)?;
+        let right = self.evaluate(&binary_expr.right)?;
+        let operator = &binary_expr.operator;
+
+        match operator.kind() {
+
+            // Arithmetic binary operations
+            TokenKind::Plus => match (left, right) {
+                (LoxObject::Number(left), LoxObject::Number(right)) => Ok(LoxObject::Number(left + right)),
+                (LoxObject::String(left), LoxObject::String(right)) => {
+                    self.allocate(left.len() + right.len(), operator)?;
+                    Ok(LoxObject::String(left + &right))
                 }
+                _ => Err(RuntimeError::new(operator, "Operands must be two numbers or two strings.".to_string())),
+            },
 
-                todo!() // syntax error
+            TokenKind::Minus => {
+                let (left, right) = Interpreter::number_operands(operator, &left, &right)?;
+                Ok(LoxObject::Number(left - right))
             }
 
-            TokenType::Slash => return LoxObject::Number(left.to_number() / right.to_number()),
+            TokenKind::Slash => {
+                let (left, right) = Interpreter::number_operands(operator, &left, &right)?;
+                Ok(LoxObject::Number(left / right))
+            }
 
-            TokenType::Star => return LoxObject::Number(left.to_number() * right.to_number()),
+            TokenKind::Star => {
+                let (left, right) = Interpreter::number_operands(operator, &left, &right)?;
+                Ok(LoxObject::Number(left * right))
+            }
 
             // Comparison binary operations
-            TokenType::Greater => return LoxObject::Boolean(left.to_number() > right.to_number()),
-            TokenType::GreaterEqual => return LoxObject::Boolean(left.to_number() >= right.to_number()),
-            TokenType::Less => return LoxObject::Boolean(left.to_number() < right.to_number()),
-            TokenType::LessEqual => return LoxObject::Boolean(left.to_number() <= right.to_number()),
+            TokenKind::Greater | TokenKind::GreaterEqual | TokenKind::Less | TokenKind::LessEqual => {
+                let (left, right) = Interpreter::number_operands(operator, &left, &right)?;
+                let result = match operator.kind() {
+                    TokenKind::Greater => left > right,
+                    TokenKind::GreaterEqual => left >= right,
+                    TokenKind::Less => left < right,
+                    _ => left <= right,
+                };
+                Ok(LoxObject::Boolean(result))
+            }
 
             // Equality
-            TokenType::EqualEqual => return LoxObject::Boolean(left == right),
-            TokenType::BangEqual => return LoxObject::Boolean(left != right),
+            TokenKind::EqualEqual => Ok(LoxObject::Boolean(left == right)),
+            TokenKind::BangEqual => Ok(LoxObject::Boolean(left != right)),
 
             _ => panic!("interpreter internal error"),
         }
     }
 
-    fn process_grouping_expr(&self, grouping_expr: &GroupingExpr) -> LoxObject {
-        return self.evaluate(grouping_expr.expression.clone());
+    fn process_call_expr(&mut self, call_expr: &CallExpr) -> Result<LoxObject, RuntimeError> {
+        let callee = self.evaluate(&call_expr.callee)?;
+
+        let mut arguments = Vec::with_capacity(call_expr.arguments.len());
+        for argument in &call_expr.arguments {
+            arguments.push(self.evaluate(argument)?);
+        }
+
+        self.call(callee, arguments, &call_expr.paren)
+    }
+
+    fn process_get_expr(&mut self, get_expr: &GetExpr) -> Result<LoxObject, RuntimeError> {
+        match self.evaluate(&get_expr.object)? {
+            LoxObject::Instance(instance) => LoxInstance::get(&instance, &get_expr.name),
+            _ => Err(RuntimeError::new(&get_expr.name, "Only instances have properties.".to_string())),
+        }
+    }
+
+    fn process_grouping_expr(&mut self, grouping_expr: &GroupingExpr) -> Result<LoxObject, RuntimeError> {
+        self.evaluate(&grouping_expr.expression)
+    }
+
+    fn process_literal_expr(&mut self, literal_expr: &LiteralExpr) -> Result<LoxObject, RuntimeError> {
+        Ok(match &literal_expr.value {
+            LiteralValue::Boolean(value) => LoxObject::Boolean(*value),
+            LiteralValue::Number(value) => LoxObject::Number(*value),
+            LiteralValue::String(value) => LoxObject::String(value.clone()),
+            LiteralValue::Nil => LoxObject::Nil,
+        })
+    }
+
+    fn process_logical_expr(&mut self, logical_expr: &LogicalExpr) -> Result<LoxObject, RuntimeError> {
+        let left = self.evaluate(&logical_expr.left)?;
+
+        match logical_expr.operator.kind() {
+            TokenKind::Or if left.is_truthy() => Ok(left),
+            TokenKind::And if !left.is_truthy() => Ok(left),
+            _ => self.evaluate(&logical_expr.right),
+        }
+    }
+
+    fn process_set_expr(&mut self, set_expr: &SetExpr) -> Result<LoxObject, RuntimeError> {
+        let instance = match self.evaluate(&set_expr.object)? {
+            LoxObject::Instance(instance) => instance,
+            _ => return Err(RuntimeError::new(&set_expr.name, "Only instances have fields.".to_string())),
+        };
+
+        let value = self.evaluate(&set_expr.value)?;
+        if instance.borrow().field(set_expr.name.lexeme()).is_none() {
+            self.allocate(set_expr.name.lexeme().len() + mem::size_of::<LoxObject>(), &set_expr.name)?;
+        }
+        LoxInstance::set(&instance, &set_expr.name, value.clone())?;
+        Ok(value)
+    }
+
+    // `super` and `this` are ordinary names in the scopes a class sets up around its methods
+    fn process_super_expr(&mut self, super_expr: &SuperExpr) -> Result<LoxObject, RuntimeError> {
+        let superclass = self.environment.borrow().get(&super_expr.keyword)?;
+        let instance = self.environment.borrow().lookup("this");
+
+        match (superclass, instance) {
+            (LoxObject::Class(superclass), Some(LoxObject::Instance(instance))) => {
+                match superclass.find_method(super_expr.method.lexeme()) {
+                    Some(method) => Ok(method.bind(super_expr.method.lexeme(), instance)),
+                    None => {
+                        let message = format!("Undefined property '{}'.", super_expr.method.lexeme());
+                        Err(RuntimeError::new(&super_expr.method, message))
+                    }
+                }
+            }
+            _ => Err(RuntimeError::new(&super_expr.keyword, "Can't use 'super' outside of a method.".to_string())),
+        }
+    }
+
+    fn process_this_expr(&mut self, this_expr: &ThisExpr) -> Result<LoxObject, RuntimeError> {
+        self.environment
+            .borrow()
+            .lookup("this")
+            .ok_or_else(|| RuntimeError::new(&this_expr.keyword, "Can't use 'this' outside of a class.".to_string()))
+    }
+
+    fn process_variable_expr(&mut self, variable_expr: &VariableExpr) -> Result<LoxObject, RuntimeError> {
+        self.environment.borrow().get(&variable_expr.name)
+    }
+
+}
+
+impl StatementProcessor<Result<(), Unwind>> for Interpreter {
+
+    fn process_block_stmt(&mut self, block_stmt: &BlockStmt) -> Result<(), Unwind> {
+        let environment = Environment::with_enclosing(self.environment.clone());
+        self.execute_block(&block_stmt.statements, environment)
+    }
+
+    fn process_class_stmt(&mut self, class_stmt: &ClassStmt) -> Result<(), Unwind> {
+        let superclass = match &class_stmt.superclass {
+            Some(expression) => match self.evaluate(expression)? {
+                LoxObject::Class(superclass) => Some(superclass),
+                _ => {
+                    let message = "Superclass must be a class.".to_string();
+                    return Err(RuntimeError::new(&class_stmt.name, message).into());
+                }
+            },
+            None => None,
+        };
+
+        // Methods of a subclass close over a scope holding `super`
+        let mut closure = self.environment.clone();
+        if let Some(superclass) = &superclass {
+            let mut environment = Environment::with_enclosing(closure);
+            environment.define("super", LoxObject::Class(superclass.clone()));
+            closure = Rc::new(RefCell::new(environment));
+        }
+
+        let mut methods = HashMap::new();
+        for method in &class_stmt.methods {
+            let is_initializer = method.name.lexeme() == "init";
+            let function = LoxFunction::new(method.clone(), closure.clone(), is_initializer);
+            methods.insert(method.name.lexeme().to_string(), Rc::new(function));
+        }
+
+        let class = LoxClass::new(class_stmt.name.lexeme().to_string(), superclass, methods);
+        self.environment.borrow_mut().define(class_stmt.name.lexeme(), LoxObject::Class(Rc::new(class)));
+        Ok(())
+    }
+
+    fn process_expression_stmt(&mut self, expression_stmt: &ExpressionStmt) -> Result<(), Unwind> {
+        self.evaluate(&expression_stmt.expression)?;
+        Ok(())
+    }
+
+    fn process_function_stmt(&mut self, function_stmt: &Rc<FunctionStmt>) -> Result<(), Unwind> {
+        let function = LoxFunction::new(function_stmt.clone(), self.environment.clone(), false);
+        self.environment.borrow_mut().define(function_stmt.name.lexeme(), LoxObject::Function(Rc::new(function)));
+        Ok(())
+    }
+
+    fn process_if_stmt(&mut self, if_stmt: &IfStmt) -> Result<(), Unwind> {
+        if self.evaluate(&if_stmt.condition)?.is_truthy() {
+            self.execute(&if_stmt.then_branch)
+        } else if let Some(else_branch) = &if_stmt.else_branch {
+            self.execute(else_branch)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn process_print_stmt(&mut self, print_stmt: &PrintStmt) -> Result<(), Unwind> {
+        let value = self.evaluate(&print_stmt.expression)?;
+        // As with diagnostics, output that can't be written is dropped
+        let _ = writeln!(self.output.borrow_mut(), "{}", value);
+        Ok(())
+    }
+
+    fn process_return_stmt(&mut self, return_stmt: &ReturnStmt) -> Result<(), Unwind> {
+        let value = match &return_stmt.value {
+            Some(value) => self.evaluate(value)?,
+            None => LoxObject::Nil,
+        };
+
+        Err(Unwind::Return(value))
+    }
+
+    fn process_throw_stmt(&mut self, throw_stmt: &ThrowStmt) -> Result<(), Unwind> {
+        let value = self.evaluate(&throw_stmt.value)?;
+        let mut error = RuntimeError::new(&throw_stmt.keyword, value.to_string());
+
+        // An Error keeps the line it was first thrown from, so rethrowing it reports that line
+        if let Some(instance) = self.error_instance(&value) {
+            let mut instance = instance.borrow_mut();
+            match instance.field("line") {
+                Some(LoxObject::Number(line)) => error.line = line as usize,
+                _ => instance.set_field("line", LoxObject::Number(error.line as f32)),
+            }
+            if let Some(message) = instance.field("message") {
+                error.message = message.to_string();
+            }
+        }
+
+        error.value = Some(value);
+        Err(error.into())
     }
 
-    fn process_string_literal_expr(&self, string_literal_expr: &StringLiteral) -> LoxObject {
-        return LoxObject::String(string_literal_expr.value.clone());
+    // A return leaves through the finally block as an error does. Only a limit the host imposed
+    // skips it.
+    fn process_try_stmt(&mut self, try_stmt: &TryStmt) -> Result<(), Unwind> {
+        let environment = Environment::with_enclosing(self.environment.clone());
+        let result = match (self.execute_block(&try_stmt.body, environment), &try_stmt.catch) {
+            (Err(Unwind::Error(error)), Some(catch)) if error.is_catchable() => {
+                let error = self.error_value(self.trace(error));
+                let mut environment = Environment::with_enclosing(self.environment.clone());
+                environment.define(catch.name.lexeme(), error);
+                self.execute_block(&catch.body, environment)
+            }
+            (result, _) => result,
+        };
+
+        if let Err(Unwind::Error(error)) = &result {
+            if !error.is_catchable() {
+                return result;
+            }
+        }
+
+        if let Some(finally) = &try_stmt.finally {
+            let environment = Environment::with_enclosing(self.environment.clone());
+            self.execute_block(finally, environment)?;
+        }
+
+        result
     }
 
-    fn process_number_literal_expr(&self, number_literal_expr: &NumberLiteral) -> LoxObject {
-        return LoxObject::Number(number_literal_expr.value);
+    fn process_var_stmt(&mut self, var_stmt: &VarStmt) -> Result<(), Unwind> {
+        let value = match &var_stmt.initializer {
+            Some(initializer) => self.evaluate(initializer)?,
+            None => LoxObject::Nil,
+        };
+
+        self.environment.borrow_mut().define(var_stmt.name.lexeme(), value);
+        Ok(())
     }
 
-    fn process_nil_literal_expr(&self, nil_literal_expr: &NilLiteral) -> LoxObject {
-        return LoxObject::Nil;
+    fn process_while_stmt(&mut self, while_stmt: &WhileStmt) -> Result<(), Unwind> {
+        while self.evaluate(&while_stmt.condition)?.is_truthy() {
+            self.execute(&while_stmt.body)?;
+        }
+
+        Ok(())
     }
 
-}
\ No newline at end of file
+}
@@ -1,7 +1,73 @@
-pub fn error(line: usize, message: &str) {
-    report(line, "".to_string(), message);
+// rlox as a library. Lox is the entry point for programs embedding the interpreter. The modules
+// behind it are public for the rlox binary and for tools that work with the syntax tree.
+
+pub mod ast_json;
+pub mod ast_printer;
+pub mod cst;
+pub mod environment;
+pub mod expr;
+pub mod formatter;
+pub mod host_class;
+pub mod interpreter;
+pub mod lox_callable;
+pub mod lox_class;
+pub mod lox_function;
+pub mod lox_object;
+pub mod lox_serde;
+pub mod natives;
+pub mod parser;
+pub mod permissions;
+pub mod scanner;
+pub mod stmt;
+mod lox;
+
+pub use crate::host_class::HostClass;
+pub use crate::interpreter::InterruptHandle;
+pub use crate::lox::{Lox, LoxError};
+pub use crate::lox_object::{ConversionError, LoxObject};
+pub use crate::lox_serde::{from_lox, to_lox, SerdeError};
+pub use crate::permissions::Permissions;
+
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+// Where program output or diagnostics are written. It is shared so that an embedder can keep a
+// handle on it and read back what was written, e.g. with an Rc<RefCell<Vec<u8>>>.
+pub type Sink = Rc<RefCell<dyn Write>>;
+
+pub fn stdout_sink() -> Sink {
+    Rc::new(RefCell::new(io::stdout()))
+}
+
+// Where diagnostics go unless sent elsewhere, so they don't mix with what the program prints
+pub fn stderr_sink() -> Sink {
+    Rc::new(RefCell::new(io::stderr()))
+}
+
+// How much Rust stack is assumed unless a host says otherwise: what Rust gives a spawned thread
+pub const DEFAULT_STACK_SIZE: usize = 2 * 1024 * 1024;
+
+// Stack kept back for the frames between two checks and for the host's own frames
+const STACK_RESERVE: usize = 256 * 1024;
+
+// Where the Rust stack is now. Parsing and running recurse, and compare this with where they
+// started to stop before they use up the stack.
+pub(crate) fn stack_position() -> usize {
+    let marker = 0u8;
+    std::hint::black_box(&marker) as *const u8 as usize
+}
+
+pub(crate) fn stack_exhausted(start: usize, stack_size: usize) -> bool {
+    start.abs_diff(stack_position()) > stack_size.saturating_sub(STACK_RESERVE)
+}
+
+pub fn error(sink: &Sink, line: usize, message: &str) {
+    report(sink, line, "".to_string(), message);
 }
 
-pub fn report(line: usize, which: String, message: &str) {
-    println!("[line: {}] Error: {} : {}", line, which, message);
+// A sink that can't be written to, such as a closed pipe, loses the message rather than
+// stopping the program
+pub fn report(sink: &Sink, line: usize, which: String, message: &str) {
+    let _ = writeln!(sink.borrow_mut(), "[line: {}] Error: {} : {}", line, which, message);
 }
\ No newline at end of file
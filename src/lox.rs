@@ -0,0 +1,202 @@
+use crate::host_class::HostClass;
+use crate::interpreter::{self, InterruptHandle, Interpreter, RuntimeError, RuntimeErrorKind};
+use crate::lox_callable::NativeFunction;
+use crate::lox_object::LoxObject;
+use crate::parser::{self, Parser};
+use crate::permissions::Permissions;
+use crate::scanner::Scanner;
+use crate::{Sink, DEFAULT_STACK_SIZE};
+use std::error::Error;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Duration;
+use std::{fmt, fs, io};
+
+// An rlox interpreter for embedding in a Rust program. Globals, functions and classes defined by
+// one call stay defined for the next, as in the REPL.
+//
+//   let mut lox = Lox::new();
+//   lox.register_fn("double", 1, |arguments| Ok(LoxObject::from(f32::try_from(&arguments[0])? * 2.0)));
+//   lox.eval("var x = double(21);")?;
+//   assert_eq!(lox.get_global("x"), Some(LoxObject::Number(42.0)));
+pub struct Lox {
+    interpreter: Interpreter,
+    max_parse_depth: usize,
+    stack_size: usize,
+}
+
+#[derive(Debug)]
+pub enum LoxError {
+    Io(io::Error),
+    // The details have been written to the diagnostics sink
+    Syntax,
+    Runtime(RuntimeError),
+    // The script ran for longer than the step budget allows
+    BudgetExhausted(RuntimeError),
+    // Stopped through an InterruptHandle or by the timeout
+    Interrupted(RuntimeError),
+}
+
+impl Lox {
+
+    pub fn new() -> Lox {
+        Lox { interpreter: Interpreter::new(), max_parse_depth: parser::DEFAULT_MAX_DEPTH, stack_size: DEFAULT_STACK_SIZE }
+    }
+
+    // Runs Lox source. If it ends in an expression without a semicolon, returns that
+    // expression's value, otherwise nil.
+    pub fn eval(&mut self, source: &str) -> Result<LoxObject, LoxError> {
+        let input = self.parser(source.to_string()).parse_repl().ok_or(LoxError::Syntax)?;
+
+        self.interpreter.set_file("<eval>");
+        self.interpreter.reset_usage();
+        self.interpreter.execute_statements(&input.statements).map_err(LoxError::from)?;
+
+        match input.expression {
+            Some(expression) => self.interpreter.evaluate_expression(&expression).map_err(LoxError::from),
+            None => Ok(LoxObject::Nil),
+        }
+    }
+
+    pub fn run_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), LoxError> {
+        let source = fs::read_to_string(&path).map_err(LoxError::Io)?;
+        let statements = self.parser(source).parse().ok_or(LoxError::Syntax)?;
+
+        self.interpreter.set_file(&path.as_ref().display().to_string());
+        self.interpreter.reset_usage();
+        self.interpreter.execute_statements(&statements).map_err(LoxError::from)
+    }
+
+    // Limits each call to eval or run_file to this many steps, where a step is one statement
+    // executed or expression evaluated. A script that goes over fails with BudgetExhausted.
+    pub fn set_max_steps(&mut self, max_steps: Option<u64>) {
+        self.interpreter.set_max_steps(max_steps);
+    }
+
+    // An allocation budget for each call to eval or run_file: the bytes it may allocate for
+    // strings, lists and instances in all. Memory freed along the way is not given back, so this
+    // bounds how much a script allocates rather than how much it holds at once. A script that
+    // goes over fails with an AllocationExhausted runtime error.
+    pub fn set_max_allocation(&mut self, max_allocation: Option<usize>) {
+        self.interpreter.set_max_allocation(max_allocation);
+    }
+
+    // What scripts may do outside the interpreter: read or write files, read environment
+    // variables, read the clock or run programs. Only the clock is allowed by default.
+    pub fn set_permissions(&mut self, permissions: Permissions) {
+        self.interpreter.set_permissions(permissions);
+    }
+
+    // Limits how long each call to eval or run_file may run. A script that runs for longer fails
+    // with Interrupted.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.interpreter.set_timeout(timeout);
+    }
+
+    // A handle that another thread can use to stop the script currently running
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.interpreter.interrupt_handle()
+    }
+
+    // How deeply Lox calls may nest before the script fails with a "Stack overflow." runtime error
+    pub fn set_max_call_depth(&mut self, max_call_depth: usize) {
+        self.interpreter.set_max_call_depth(max_call_depth);
+    }
+
+    // How much stack the thread running scripts has, 2MB unless set. Source nested deeply enough
+    // to use it up is a syntax error, and a script that recurses that deeply fails with a "Stack
+    // overflow." runtime error, so a host that wants deep recursion runs scripts on a thread with a
+    // bigger stack and says so here.
+    pub fn set_stack_size(&mut self, stack_size: usize) {
+        self.stack_size = stack_size;
+        self.interpreter.set_stack_size(stack_size);
+    }
+
+    // How deeply statements and expressions may nest in source before it is a syntax error
+    pub fn set_max_parse_depth(&mut self, max_parse_depth: usize) {
+        self.max_parse_depth = max_parse_depth;
+    }
+
+    // Where print writes. Standard output by default.
+    pub fn set_output(&mut self, output: Sink) {
+        self.interpreter.set_output(output);
+    }
+
+    // Where syntax and runtime errors are reported. Standard error by default.
+    pub fn set_diagnostics(&mut self, diagnostics: Sink) {
+        self.interpreter.set_diagnostics(diagnostics);
+    }
+
+    pub fn set_global<V: Into<LoxObject>>(&mut self, name: &str, value: V) {
+        self.interpreter.define_global(name, value.into());
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<LoxObject> {
+        self.interpreter.global(name)
+    }
+
+    // Makes a Rust closure callable from Lox as a global function. Lox checks the number of
+    // arguments before calling it. An Err becomes a runtime error at the call.
+    pub fn register_fn<F>(&mut self, name: &str, arity: usize, function: F)
+    where
+        F: Fn(&[LoxObject]) -> Result<LoxObject, String> + 'static,
+    {
+        let native = NativeFunction::new(name, arity, Rc::new(move |_, arguments| function(&arguments)));
+        self.interpreter.define_global(name, LoxObject::Native(Rc::new(native)));
+    }
+
+    // Makes a Rust type available to Lox as a global class
+    pub fn register_class<T: 'static>(&mut self, class: HostClass<T>) {
+        let name = class.name().to_string();
+        self.interpreter.define_global(&name, LoxObject::Class(Rc::new(class.into_class())));
+    }
+
+    fn parser(&self, source: String) -> Parser<Scanner> {
+        let mut parser = Parser::new(Scanner::new(source));
+        parser.set_diagnostics(self.interpreter.diagnostics());
+        parser.set_max_depth(self.max_parse_depth);
+        parser.set_stack_size(self.stack_size);
+        parser
+    }
+
+}
+
+impl Default for Lox {
+
+    fn default() -> Lox {
+        Lox::new()
+    }
+}
+
+impl fmt::Display for LoxError {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoxError::Io(e) => write!(f, "{}", e),
+            LoxError::Syntax => write!(f, "Syntax error"),
+            LoxError::Runtime(e) | LoxError::BudgetExhausted(e) | LoxError::Interrupted(e) => {
+                write!(f, "[line {}] {}", e.line(), e.message())?;
+
+                let mut trace = String::new();
+                interpreter::write_stack(&mut trace, e.stack())?;
+                if !trace.is_empty() {
+                    write!(f, "\n{}", trace.trim_end())?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl From<RuntimeError> for LoxError {
+
+    fn from(error: RuntimeError) -> LoxError {
+        match error.kind() {
+            RuntimeErrorKind::BudgetExhausted => LoxError::BudgetExhausted(error),
+            RuntimeErrorKind::Interrupted => LoxError::Interrupted(error),
+            RuntimeErrorKind::Error | RuntimeErrorKind::AllocationExhausted => LoxError::Runtime(error),
+        }
+    }
+}
+
+impl Error for LoxError {}
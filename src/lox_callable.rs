@@ -0,0 +1,54 @@
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::lox_object::LoxObject;
+use crate::scanner::Token;
+use std::fmt;
+use std::rc::Rc;
+
+// Anything Lox code can call: functions, classes and functions written in Rust. The interpreter
+// checks the number of arguments against the arity before calling.
+pub trait LoxCallable {
+    fn arity(&self) -> usize;
+
+    // The closing parenthesis of the call locates any error
+    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<LoxObject>, paren: &Token) -> Result<LoxObject, RuntimeError>;
+}
+
+// What a native function does. An Err is raised as a runtime error at the call.
+pub type NativeFn = dyn Fn(&mut Interpreter, Vec<LoxObject>) -> Result<LoxObject, String>;
+
+pub struct NativeFunction {
+    name: String,
+    arity: usize,
+    function: Rc<NativeFn>,
+}
+
+impl NativeFunction {
+
+    pub fn new(name: &str, arity: usize, function: Rc<NativeFn>) -> NativeFunction {
+        NativeFunction { name: name.to_string(), arity, function }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+}
+
+impl LoxCallable for NativeFunction {
+
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<LoxObject>, paren: &Token) -> Result<LoxObject, RuntimeError> {
+        (self.function)(interpreter, arguments).map_err(|message| RuntimeError::new(paren, message))
+    }
+
+}
+
+impl fmt::Debug for NativeFunction {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "NativeFunction({}/{})", self.name, self.arity)
+    }
+}
@@ -0,0 +1,242 @@
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::lox_callable::{LoxCallable, NativeFunction};
+use crate::lox_function::LoxFunction;
+use crate::lox_object::LoxObject;
+use crate::scanner::Token;
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::mem;
+use std::rc::Rc;
+
+// Members of a class defined by the host program rather than in Lox. They are given the
+// instance, whose host data holds the Rust value behind it.
+pub type HostFn = dyn Fn(&Rc<RefCell<LoxInstance>>, Vec<LoxObject>) -> Result<LoxObject, String>;
+pub type HostGetter = dyn Fn(&Rc<RefCell<LoxInstance>>) -> Result<LoxObject, String>;
+pub type HostSetter = dyn Fn(&Rc<RefCell<LoxInstance>>, LoxObject) -> Result<(), String>;
+
+#[derive(Clone)]
+pub enum Method {
+    Lox(Rc<LoxFunction>),
+    Host { arity: usize, function: Rc<HostFn> },
+}
+
+impl Method {
+
+    pub fn arity(&self) -> usize {
+        match self {
+            Method::Lox(function) => function.arity(),
+            Method::Host { arity, .. } => *arity,
+        }
+    }
+
+    // The method as a value with the instance it was looked up on built in
+    pub fn bind(&self, name: &str, instance: Rc<RefCell<LoxInstance>>) -> LoxObject {
+        match self {
+            Method::Lox(function) => LoxObject::Function(Rc::new(function.bind(instance))),
+            Method::Host { arity, function } => {
+                let function = function.clone();
+                let bound = move |_: &mut Interpreter, arguments| function(&instance, arguments);
+                LoxObject::Native(Rc::new(NativeFunction::new(name, *arity, Rc::new(bound))))
+            }
+        }
+    }
+
+}
+
+impl fmt::Debug for Method {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Method::Lox(function) => write!(f, "{:?}", function),
+            Method::Host { arity, .. } => write!(f, "HostMethod({})", arity),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct HostMembers {
+    pub methods: HashMap<String, Method>,
+    pub getters: HashMap<String, Rc<HostGetter>>,
+    pub setters: HashMap<String, Rc<HostSetter>>,
+}
+
+pub struct LoxClass {
+    name: String,
+    superclass: Option<Rc<LoxClass>>,
+    methods: HashMap<String, Method>,
+    getters: HashMap<String, Rc<HostGetter>>,
+    setters: HashMap<String, Rc<HostSetter>>,
+}
+
+impl LoxClass {
+
+    pub fn new(name: String, superclass: Option<Rc<LoxClass>>, methods: HashMap<String, Rc<LoxFunction>>) -> LoxClass {
+        let methods = methods.into_iter().map(|(name, method)| (name, Method::Lox(method))).collect();
+        LoxClass { name, superclass, methods, getters: HashMap::new(), setters: HashMap::new() }
+    }
+
+    pub fn new_host(name: String, members: HostMembers) -> LoxClass {
+        let HostMembers { methods, getters, setters } = members;
+        LoxClass { name, superclass: None, methods, getters, setters }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // Whether this is the given class or inherits from it
+    pub fn is_subclass_of(&self, class: &LoxClass) -> bool {
+        std::ptr::eq(self, class) || self.superclass.as_ref().is_some_and(|superclass| superclass.is_subclass_of(class))
+    }
+
+    // Searches this class and then its superclasses
+    pub fn find_method(&self, name: &str) -> Option<Method> {
+        match self.methods.get(name) {
+            Some(method) => Some(method.clone()),
+            None => self.superclass.as_ref().and_then(|superclass| superclass.find_method(name)),
+        }
+    }
+
+    fn find_getter(&self, name: &str) -> Option<Rc<HostGetter>> {
+        match self.getters.get(name) {
+            Some(getter) => Some(getter.clone()),
+            None => self.superclass.as_ref().and_then(|superclass| superclass.find_getter(name)),
+        }
+    }
+
+    fn find_setter(&self, name: &str) -> Option<Rc<HostSetter>> {
+        match self.setters.get(name) {
+            Some(setter) => Some(setter.clone()),
+            None => self.superclass.as_ref().and_then(|superclass| superclass.find_setter(name)),
+        }
+    }
+
+    // Names of the methods and host properties in this class and its superclasses
+    pub fn method_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.methods.keys().chain(self.getters.keys()).cloned().collect();
+        if let Some(superclass) = &self.superclass {
+            names.extend(superclass.method_names());
+        }
+        names
+    }
+
+}
+
+impl fmt::Debug for LoxClass {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LoxClass")
+            .field("name", &self.name)
+            .field("superclass", &self.superclass)
+            .field("methods", &self.methods)
+            .finish()
+    }
+}
+
+// Calling a class makes an instance of it. The instance needs a reference to its class, so this
+// is implemented on the shared class.
+impl LoxCallable for Rc<LoxClass> {
+
+    // A class takes the arguments of its initializer
+    fn arity(&self) -> usize {
+        self.find_method("init").map_or(0, |initializer| initializer.arity())
+    }
+
+    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<LoxObject>, paren: &Token) -> Result<LoxObject, RuntimeError> {
+        interpreter.allocate(mem::size_of::<LoxInstance>(), paren)?;
+        let instance = Rc::new(RefCell::new(LoxInstance::new(self.clone())));
+
+        match self.find_method("init") {
+            Some(Method::Lox(initializer)) => {
+                initializer.bind(instance.clone()).call(interpreter, arguments, paren)?;
+            }
+            Some(Method::Host { function, .. }) => {
+                function(&instance, arguments).map_err(|message| RuntimeError::new(paren, message))?;
+            }
+            None => {}
+        }
+
+        Ok(LoxObject::Instance(instance))
+    }
+}
+
+#[derive(Debug)]
+pub struct LoxInstance {
+    class: Rc<LoxClass>,
+    fields: HashMap<String, LoxObject>,
+    // The Rust value behind an instance of a host class, once its initializer has run
+    host_data: Option<Rc<dyn Any>>,
+}
+
+impl LoxInstance {
+
+    pub fn new(class: Rc<LoxClass>) -> LoxInstance {
+        LoxInstance { class, fields: HashMap::new(), host_data: None }
+    }
+
+    pub fn class(&self) -> &Rc<LoxClass> {
+        &self.class
+    }
+
+    pub fn field(&self, name: &str) -> Option<LoxObject> {
+        self.fields.get(name).cloned()
+    }
+
+    pub fn field_names(&self) -> Vec<String> {
+        self.fields.keys().cloned().collect()
+    }
+
+    pub fn fields(&self) -> Vec<(String, LoxObject)> {
+        self.fields.iter().map(|(name, value)| (name.clone(), value.clone())).collect()
+    }
+
+    // Sets a field directly, bypassing any host setter
+    pub fn set_field(&mut self, name: &str, value: LoxObject) {
+        self.fields.insert(name.to_string(), value);
+    }
+
+    pub fn host_data(&self) -> Option<Rc<dyn Any>> {
+        self.host_data.clone()
+    }
+
+    pub fn set_host_data(&mut self, data: Rc<dyn Any>) {
+        self.host_data = Some(data);
+    }
+
+    // Fields shadow host properties, which shadow methods. Methods come back bound to the
+    // instance.
+    pub fn get(instance: &Rc<RefCell<LoxInstance>>, name: &Token) -> Result<LoxObject, RuntimeError> {
+        if let Some(value) = instance.borrow().fields.get(name.lexeme()) {
+            return Ok(value.clone());
+        }
+
+        let class = instance.borrow().class.clone();
+
+        if let Some(getter) = class.find_getter(name.lexeme()) {
+            return getter(instance).map_err(|message| RuntimeError::new(name, message));
+        }
+
+        match class.find_method(name.lexeme()) {
+            Some(method) => Ok(method.bind(name.lexeme(), instance.clone())),
+            None => Err(RuntimeError::new(name, format!("Undefined property '{}'.", name.lexeme()))),
+        }
+    }
+
+    // Assigning to a host property calls its setter. Anything else sets a field.
+    pub fn set(instance: &Rc<RefCell<LoxInstance>>, name: &Token, value: LoxObject) -> Result<(), RuntimeError> {
+        let class = instance.borrow().class.clone();
+
+        if let Some(setter) = class.find_setter(name.lexeme()) {
+            return setter(instance, value).map_err(|message| RuntimeError::new(name, message));
+        }
+
+        if class.find_getter(name.lexeme()).is_some() {
+            return Err(RuntimeError::new(name, format!("Property '{}' is read-only.", name.lexeme())));
+        }
+
+        instance.borrow_mut().fields.insert(name.lexeme().to_string(), value);
+        Ok(())
+    }
+}
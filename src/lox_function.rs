@@ -0,0 +1,64 @@
+use crate::environment::Environment;
+use crate::interpreter::{Interpreter, RuntimeError, Unwind};
+use crate::lox_callable::LoxCallable;
+use crate::lox_class::LoxInstance;
+use crate::lox_object::LoxObject;
+use crate::scanner::Token;
+use crate::stmt::FunctionStmt;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// A function or method together with the scope it was declared in
+#[derive(Debug)]
+pub struct LoxFunction {
+    declaration: Rc<FunctionStmt>,
+    closure: Rc<RefCell<Environment>>,
+    // Initializers always return their instance, even from a bare `return;`
+    is_initializer: bool,
+}
+
+impl LoxFunction {
+
+    pub fn new(declaration: Rc<FunctionStmt>, closure: Rc<RefCell<Environment>>, is_initializer: bool) -> LoxFunction {
+        LoxFunction { declaration, closure, is_initializer }
+    }
+
+    pub fn name(&self) -> &str {
+        self.declaration.name.lexeme()
+    }
+
+    // A copy of a method whose scope has `this` bound to the instance
+    pub fn bind(&self, instance: Rc<RefCell<LoxInstance>>) -> LoxFunction {
+        let mut environment = Environment::with_enclosing(self.closure.clone());
+        environment.define("this", LoxObject::Instance(instance));
+
+        LoxFunction::new(self.declaration.clone(), Rc::new(RefCell::new(environment)), self.is_initializer)
+    }
+
+}
+
+impl LoxCallable for LoxFunction {
+
+    fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+
+    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<LoxObject>, _paren: &Token) -> Result<LoxObject, RuntimeError> {
+        let mut environment = Environment::with_enclosing(self.closure.clone());
+        for (param, argument) in self.declaration.params.iter().zip(arguments) {
+            environment.define(param.lexeme(), argument);
+        }
+
+        let value = match interpreter.execute_block(&self.declaration.body, environment) {
+            Ok(()) => LoxObject::Nil,
+            Err(Unwind::Return(value)) => value,
+            Err(Unwind::Error(error)) => return Err(error),
+        };
+
+        if self.is_initializer {
+            return Ok(self.closure.borrow().lookup("this").unwrap_or(LoxObject::Nil));
+        }
+
+        Ok(value)
+    }
+}
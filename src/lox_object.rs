@@ -1,72 +1,248 @@
-#[derive(Debug)]
+use crate::lox_callable::NativeFunction;
+use crate::lox_class::{LoxClass, LoxInstance};
+use crate::lox_function::LoxFunction;
+use std::cell::RefCell;
+use std::convert::TryFrom;
+use std::fmt;
+use std::rc::Rc;
+
+// Functions and classes are shared, and instances are shared and mutable, so copying a
+// LoxObject only copies the reference for those
+#[derive(Clone, Debug)]
 pub enum LoxObject {
     Boolean(bool),
     Number(f32),
     String(String),
     Nil,
+    Function(Rc<LoxFunction>),
+    Native(Rc<NativeFunction>),
+    Class(Rc<LoxClass>),
+    Instance(Rc<RefCell<LoxInstance>>),
+    // Only built-in functions make lists, and they can't be changed
+    List(Rc<Vec<LoxObject>>),
 }
 
 impl LoxObject {
 
-    pub fn to_number(&self) -> f32 {
-        match *self {
-            LoxObject::Number(val) => return val,
-            _ => panic!("invalid cast"),
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            LoxObject::Boolean(_) => "boolean",
+            LoxObject::Number(_) => "number",
+            LoxObject::String(_) => "string",
+            LoxObject::Nil => "nil",
+            LoxObject::Function(_) | LoxObject::Native(_) => "function",
+            LoxObject::Class(_) => "class",
+            LoxObject::Instance(_) => "instance",
+            LoxObject::List(_) => "list",
         }
     }
 
-    pub fn to_boolean(&self) -> bool {
+    pub fn is_truthy(&self) -> bool {
         match *self {
-            LoxObject::Boolean(val) => return val,
-            _ => panic!("invalid cast"),
+            LoxObject::Nil => false,
+            LoxObject::Boolean(val) => val,
+            _ => true,
         }
     }
 
-    pub fn to_string(&self) -> &str {
+}
+
+impl PartialEq for LoxObject {
+
+    // Values of different types are never equal. Functions, classes and instances are equal
+    // only to themselves. Lists are equal when their elements are.
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (LoxObject::Boolean(left), LoxObject::Boolean(right)) => left == right,
+            (LoxObject::Number(left), LoxObject::Number(right)) => left == right,
+            (LoxObject::String(left), LoxObject::String(right)) => left == right,
+            (LoxObject::Nil, LoxObject::Nil) => true,
+            (LoxObject::Function(left), LoxObject::Function(right)) => Rc::ptr_eq(left, right),
+            (LoxObject::Native(left), LoxObject::Native(right)) => Rc::ptr_eq(left, right),
+            (LoxObject::Class(left), LoxObject::Class(right)) => Rc::ptr_eq(left, right),
+            (LoxObject::Instance(left), LoxObject::Instance(right)) => Rc::ptr_eq(left, right),
+            (LoxObject::List(left), LoxObject::List(right)) => left == right,
+            _ => false,
+        }
+    }
+}
+
+// How print and the REPL show a value
+impl fmt::Display for LoxObject {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            LoxObject::String(val) => return &val,
-            _ => panic!("invalid cast"),
+            LoxObject::Boolean(val) => write!(f, "{}", val),
+            LoxObject::Number(val) => write!(f, "{}", val),
+            LoxObject::String(val) => write!(f, "{}", val),
+            LoxObject::Nil => write!(f, "nil"),
+            LoxObject::Function(function) => write!(f, "<fn {}>", function.name()),
+            LoxObject::Native(function) => write!(f, "<native fn {}>", function.name()),
+            LoxObject::Class(class) => write!(f, "{}", class.name()),
+            LoxObject::Instance(instance) => write!(f, "{} instance", instance.borrow().class().name()),
+            LoxObject::List(elements) => {
+                let elements: Vec<String> = elements.iter().map(|element| element.to_string()).collect();
+                write!(f, "[{}]", elements.join(", "))
+            }
         }
     }
+}
 
-    pub fn is_truthy(&self) -> bool {
-        match *self {
-            LoxObject::Nil => return false,
-            LoxObject::Boolean(val) => return val,
-            _ => return true,
+// Conversions for Rust code exchanging values with Lox -----------------------------------------
+
+impl From<bool> for LoxObject {
+    fn from(value: bool) -> LoxObject {
+        LoxObject::Boolean(value)
+    }
+}
+
+impl From<f32> for LoxObject {
+    fn from(value: f32) -> LoxObject {
+        LoxObject::Number(value)
+    }
+}
+
+impl From<f64> for LoxObject {
+    fn from(value: f64) -> LoxObject {
+        LoxObject::Number(value as f32)
+    }
+}
+
+impl From<i32> for LoxObject {
+    fn from(value: i32) -> LoxObject {
+        LoxObject::Number(value as f32)
+    }
+}
+
+impl From<String> for LoxObject {
+    fn from(value: String) -> LoxObject {
+        LoxObject::String(value)
+    }
+}
+
+impl From<&str> for LoxObject {
+    fn from(value: &str) -> LoxObject {
+        LoxObject::String(value.to_string())
+    }
+}
+
+impl From<()> for LoxObject {
+    fn from(_: ()) -> LoxObject {
+        LoxObject::Nil
+    }
+}
+
+// None is nil
+impl<T: Into<LoxObject>> From<Option<T>> for LoxObject {
+    fn from(value: Option<T>) -> LoxObject {
+        value.map_or(LoxObject::Nil, Into::into)
+    }
+}
+
+impl<T: Into<LoxObject>> From<Vec<T>> for LoxObject {
+    fn from(values: Vec<T>) -> LoxObject {
+        LoxObject::List(Rc::new(values.into_iter().map(Into::into).collect()))
+    }
+}
+
+// A Lox value that isn't the type a Rust conversion asked for
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConversionError {
+    expected: &'static str,
+    found: &'static str,
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Expected a {} but got a {}.", self.expected, self.found)
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+// So native functions, which fail with a message, can use ? on conversions
+impl From<ConversionError> for String {
+    fn from(error: ConversionError) -> String {
+        error.to_string()
+    }
+}
+
+fn mismatch<T>(expected: &'static str, found: &LoxObject) -> Result<T, ConversionError> {
+    Err(ConversionError { expected, found: found.type_name() })
+}
+
+impl TryFrom<&LoxObject> for bool {
+    type Error = ConversionError;
+
+    fn try_from(value: &LoxObject) -> Result<bool, ConversionError> {
+        match value {
+            LoxObject::Boolean(value) => Ok(*value),
+            other => mismatch("boolean", other),
         }
     }
+}
 
+impl TryFrom<&LoxObject> for f32 {
+    type Error = ConversionError;
+
+    fn try_from(value: &LoxObject) -> Result<f32, ConversionError> {
+        match value {
+            LoxObject::Number(value) => Ok(*value),
+            other => mismatch("number", other),
+        }
+    }
 }
 
-impl PartialEq for LoxObject {
+impl TryFrom<&LoxObject> for f64 {
+    type Error = ConversionError;
 
-    fn eq(&self, other: &Self) -> bool {
+    fn try_from(value: &LoxObject) -> Result<f64, ConversionError> {
+        f32::try_from(value).map(f64::from)
+    }
+}
 
-        match self {
-            LoxObject::Boolean(val) => {
-                return *val == other.to_boolean();
-            },
-
-            LoxObject::Number(val) => {
-                return *val == other.to_number();
-            },
-
-            LoxObject::String(val) => {
-                return *val == other.to_string();
-            },
-
-            LoxObject::Nil => {
-                match other {
-                    LoxObject::Nil => {
-                        return true;
-
-                    }
-                    _ => {
-                        return false;
-                    }
-                }
-            },
+impl TryFrom<&LoxObject> for String {
+    type Error = ConversionError;
+
+    fn try_from(value: &LoxObject) -> Result<String, ConversionError> {
+        match value {
+            LoxObject::String(value) => Ok(value.clone()),
+            other => mismatch("string", other),
+        }
+    }
+}
+
+impl TryFrom<LoxObject> for bool {
+    type Error = ConversionError;
+
+    fn try_from(value: LoxObject) -> Result<bool, ConversionError> {
+        bool::try_from(&value)
+    }
+}
+
+impl TryFrom<LoxObject> for f32 {
+    type Error = ConversionError;
+
+    fn try_from(value: LoxObject) -> Result<f32, ConversionError> {
+        f32::try_from(&value)
+    }
+}
+
+impl TryFrom<LoxObject> for f64 {
+    type Error = ConversionError;
+
+    fn try_from(value: LoxObject) -> Result<f64, ConversionError> {
+        f64::try_from(&value)
+    }
+}
+
+impl TryFrom<LoxObject> for String {
+    type Error = ConversionError;
+
+    fn try_from(value: LoxObject) -> Result<String, ConversionError> {
+        match value {
+            LoxObject::String(value) => Ok(value),
+            other => mismatch("string", &other),
         }
     }
 }
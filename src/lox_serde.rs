@@ -0,0 +1,696 @@
+use crate::lox_class::{LoxClass, LoxInstance};
+use crate::lox_object::LoxObject;
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::{forward_to_deserialize_any, ser, Serialize};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+// Converts between Rust data and Lox values through serde.
+//
+//   Rust                          Lox
+//   bool, numbers, char, strings  booleans, numbers, strings
+//   None, (), unit structs        nil
+//   sequences, tuples             lists
+//   structs                       instances of a class named after the struct
+//   maps                          instances of a class named Map, keys as field names
+//                                 (number keys are read back from their field names)
+//   unit enum variants            the variant name as a string
+//   other enum variants           an instance with one field named after the variant
+//
+// Numbers are single precision in Lox, so large integers lose precision on the way in.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SerdeError {
+    message: String,
+}
+
+impl SerdeError {
+    fn new(message: String) -> SerdeError {
+        SerdeError { message }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for SerdeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for SerdeError {}
+
+impl ser::Error for SerdeError {
+    fn custom<T: fmt::Display>(message: T) -> SerdeError {
+        SerdeError::new(message.to_string())
+    }
+}
+
+impl de::Error for SerdeError {
+    fn custom<T: fmt::Display>(message: T) -> SerdeError {
+        SerdeError::new(message.to_string())
+    }
+}
+
+pub fn to_lox<T: Serialize + ?Sized>(value: &T) -> Result<LoxObject, SerdeError> {
+    value.serialize(Serializer)
+}
+
+pub fn from_lox<T: DeserializeOwned>(value: &LoxObject) -> Result<T, SerdeError> {
+    T::deserialize(Deserializer::new(value))
+}
+
+// An instance of a fresh, methodless class
+fn instance(class_name: &str, fields: Vec<(String, LoxObject)>) -> LoxObject {
+    let class = Rc::new(LoxClass::new(class_name.to_string(), None, HashMap::new()));
+    let mut instance = LoxInstance::new(class);
+    for (name, value) in fields {
+        instance.set_field(&name, value);
+    }
+
+    LoxObject::Instance(Rc::new(RefCell::new(instance)))
+}
+
+// Serializing ----------------------------------------------------------------------------------
+
+pub struct Serializer;
+
+impl ser::Serializer for Serializer {
+    type Ok = LoxObject;
+    type Error = SerdeError;
+    type SerializeSeq = SerializeList;
+    type SerializeTuple = SerializeList;
+    type SerializeTupleStruct = SerializeList;
+    type SerializeTupleVariant = SerializeList;
+    type SerializeMap = SerializeFields;
+    type SerializeStruct = SerializeFields;
+    type SerializeStructVariant = SerializeFields;
+
+    fn serialize_bool(self, value: bool) -> Result<LoxObject, SerdeError> {
+        Ok(LoxObject::Boolean(value))
+    }
+
+    fn serialize_i8(self, value: i8) -> Result<LoxObject, SerdeError> {
+        Ok(LoxObject::Number(value as f32))
+    }
+
+    fn serialize_i16(self, value: i16) -> Result<LoxObject, SerdeError> {
+        Ok(LoxObject::Number(value as f32))
+    }
+
+    fn serialize_i32(self, value: i32) -> Result<LoxObject, SerdeError> {
+        Ok(LoxObject::Number(value as f32))
+    }
+
+    fn serialize_i64(self, value: i64) -> Result<LoxObject, SerdeError> {
+        Ok(LoxObject::Number(value as f32))
+    }
+
+    fn serialize_u8(self, value: u8) -> Result<LoxObject, SerdeError> {
+        Ok(LoxObject::Number(value as f32))
+    }
+
+    fn serialize_u16(self, value: u16) -> Result<LoxObject, SerdeError> {
+        Ok(LoxObject::Number(value as f32))
+    }
+
+    fn serialize_u32(self, value: u32) -> Result<LoxObject, SerdeError> {
+        Ok(LoxObject::Number(value as f32))
+    }
+
+    fn serialize_u64(self, value: u64) -> Result<LoxObject, SerdeError> {
+        Ok(LoxObject::Number(value as f32))
+    }
+
+    fn serialize_f32(self, value: f32) -> Result<LoxObject, SerdeError> {
+        Ok(LoxObject::Number(value))
+    }
+
+    fn serialize_f64(self, value: f64) -> Result<LoxObject, SerdeError> {
+        Ok(LoxObject::Number(value as f32))
+    }
+
+    fn serialize_char(self, value: char) -> Result<LoxObject, SerdeError> {
+        Ok(LoxObject::String(value.to_string()))
+    }
+
+    fn serialize_str(self, value: &str) -> Result<LoxObject, SerdeError> {
+        Ok(LoxObject::String(value.to_string()))
+    }
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<LoxObject, SerdeError> {
+        Ok(LoxObject::from(value.iter().map(|byte| *byte as f32).collect::<Vec<f32>>()))
+    }
+
+    fn serialize_none(self) -> Result<LoxObject, SerdeError> {
+        Ok(LoxObject::Nil)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<LoxObject, SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<LoxObject, SerdeError> {
+        Ok(LoxObject::Nil)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<LoxObject, SerdeError> {
+        Ok(LoxObject::Nil)
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<LoxObject, SerdeError> {
+        Ok(LoxObject::String(variant.to_string()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<LoxObject, SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<LoxObject, SerdeError> {
+        Ok(instance(name, vec![(variant.to_string(), value.serialize(self)?)]))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeList, SerdeError> {
+        Ok(SerializeList { elements: Vec::with_capacity(len.unwrap_or(0)), variant: None })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeList, SerdeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SerializeList, SerdeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeList, SerdeError> {
+        Ok(SerializeList { elements: Vec::with_capacity(len), variant: Some((name, variant)) })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<SerializeFields, SerdeError> {
+        Ok(SerializeFields::new("Map", None))
+    }
+
+    fn serialize_struct(self, name: &'static str, _len: usize) -> Result<SerializeFields, SerdeError> {
+        Ok(SerializeFields::new(name, None))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<SerializeFields, SerdeError> {
+        Ok(SerializeFields::new(variant, Some((name, variant))))
+    }
+}
+
+// The enum and variant name of an enum variant being serialized
+type Variant = Option<(&'static str, &'static str)>;
+
+fn wrap(variant: Variant, value: LoxObject) -> LoxObject {
+    match variant {
+        Some((name, variant)) => instance(name, vec![(variant.to_string(), value)]),
+        None => value,
+    }
+}
+
+pub struct SerializeList {
+    elements: Vec<LoxObject>,
+    variant: Variant,
+}
+
+impl SerializeList {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.elements.push(value.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<LoxObject, SerdeError> {
+        Ok(wrap(self.variant, LoxObject::List(Rc::new(self.elements))))
+    }
+}
+
+impl ser::SerializeSeq for SerializeList {
+    type Ok = LoxObject;
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<LoxObject, SerdeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SerializeList {
+    type Ok = LoxObject;
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<LoxObject, SerdeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeList {
+    type Ok = LoxObject;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<LoxObject, SerdeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeList {
+    type Ok = LoxObject;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<LoxObject, SerdeError> {
+        self.finish()
+    }
+}
+
+pub struct SerializeFields {
+    class_name: &'static str,
+    fields: Vec<(String, LoxObject)>,
+    key: Option<String>,
+    variant: Variant,
+}
+
+impl SerializeFields {
+    fn new(class_name: &'static str, variant: Variant) -> SerializeFields {
+        SerializeFields { class_name, fields: vec![], key: None, variant }
+    }
+
+    fn field<T: Serialize + ?Sized>(&mut self, name: String, value: &T) -> Result<(), SerdeError> {
+        self.fields.push((name, value.serialize(Serializer)?));
+        Ok(())
+    }
+
+    fn finish(self) -> Result<LoxObject, SerdeError> {
+        Ok(wrap(self.variant, instance(self.class_name, self.fields)))
+    }
+}
+
+impl ser::SerializeMap for SerializeFields {
+    type Ok = LoxObject;
+    type Error = SerdeError;
+
+    // Keys become field names, so they have to be strings or numbers
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), SerdeError> {
+        self.key = match key.serialize(Serializer)? {
+            LoxObject::String(key) => Some(key),
+            LoxObject::Number(key) => Some(key.to_string()),
+            other => return Err(SerdeError::new(format!("Map keys must be strings, not {}", other.type_name()))),
+        };
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        let key = self.key.take().ok_or_else(|| SerdeError::new("Map value without a key".to_string()))?;
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<LoxObject, SerdeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for SerializeFields {
+    type Ok = LoxObject;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, name: &'static str, value: &T) -> Result<(), SerdeError> {
+        self.field(name.to_string(), value)
+    }
+
+    fn end(self) -> Result<LoxObject, SerdeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeFields {
+    type Ok = LoxObject;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, name: &'static str, value: &T) -> Result<(), SerdeError> {
+        self.field(name.to_string(), value)
+    }
+
+    fn end(self) -> Result<LoxObject, SerdeError> {
+        self.finish()
+    }
+}
+
+// Deserializing --------------------------------------------------------------------------------
+
+// Lists and instances can contain themselves, so converting stops at this depth rather than
+// recursing forever
+const MAX_DEPTH: usize = 128;
+
+pub struct Deserializer<'a> {
+    value: &'a LoxObject,
+    // How many lists and instances enclose the value
+    depth: usize,
+}
+
+impl<'a> Deserializer<'a> {
+    pub fn new(value: &'a LoxObject) -> Deserializer<'a> {
+        Deserializer { value, depth: 0 }
+    }
+
+    fn nested(value: &LoxObject, depth: usize) -> Deserializer<'_> {
+        Deserializer { value, depth }
+    }
+
+    // The depth of the values inside this list or instance
+    fn inner_depth(&self) -> Result<usize, SerdeError> {
+        if self.depth >= MAX_DEPTH {
+            let message = format!("Can't convert a value nested more than {} deep, or that contains itself", MAX_DEPTH);
+            return Err(SerdeError::new(message));
+        }
+
+        Ok(self.depth + 1)
+    }
+}
+
+impl<'de, 'a> de::Deserializer<'de> for Deserializer<'a> {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.value {
+            LoxObject::Boolean(value) => visitor.visit_bool(*value),
+            // Whole numbers go to the visitor as integers, which float targets also accept
+            LoxObject::Number(value) if value.fract() == 0.0 && value.abs() < i64::MAX as f32 => {
+                visitor.visit_i64(*value as i64)
+            }
+            LoxObject::Number(value) => visitor.visit_f32(*value),
+            LoxObject::String(value) => visitor.visit_str(value),
+            LoxObject::Nil => visitor.visit_unit(),
+            LoxObject::List(elements) => {
+                let depth = self.inner_depth()?;
+                visitor.visit_seq(ListAccess { elements: elements.iter(), depth })
+            }
+            LoxObject::Instance(instance) => {
+                let depth = self.inner_depth()?;
+                let fields = instance.borrow().fields();
+                visitor.visit_map(FieldAccess { fields: fields.into_iter(), value: None, depth })
+            }
+            other => Err(SerdeError::new(format!("Can't convert a {} to Rust data", other.type_name()))),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.value {
+            LoxObject::Nil => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        match self.value {
+            LoxObject::String(variant) => visitor.visit_enum(variant.as_str().into_deserializer()),
+            LoxObject::Instance(instance) => {
+                let depth = self.inner_depth()?;
+                let mut fields = instance.borrow().fields();
+                match fields.pop() {
+                    Some((variant, value)) if fields.is_empty() => visitor.visit_enum(VariantAccess { variant, value, depth }),
+                    _ => Err(SerdeError::new("An enum variant must be an instance with one field".to_string())),
+                }
+            }
+            other => Err(SerdeError::new(format!("Can't convert a {} to an enum", other.type_name()))),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+struct ListAccess<'a> {
+    elements: std::slice::Iter<'a, LoxObject>,
+    depth: usize,
+}
+
+impl<'de, 'a> de::SeqAccess<'de> for ListAccess<'a> {
+    type Error = SerdeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, SerdeError> {
+        match self.elements.next() {
+            Some(element) => seed.deserialize(Deserializer::nested(element, self.depth)).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+// Fields are copied out of the instance first, so it isn't borrowed while they are converted
+struct FieldAccess {
+    fields: std::vec::IntoIter<(String, LoxObject)>,
+    value: Option<LoxObject>,
+    depth: usize,
+}
+
+impl<'de> de::MapAccess<'de> for FieldAccess {
+    type Error = SerdeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, SerdeError> {
+        match self.fields.next() {
+            Some((name, value)) => {
+                self.value = Some(value);
+                seed.deserialize(FieldName(name)).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, SerdeError> {
+        let value = self.value.take().ok_or_else(|| SerdeError::new("Field value without a name".to_string()))?;
+        seed.deserialize(Deserializer::nested(&value, self.depth))
+    }
+}
+
+// A field name as a map key. Maps serialized with number keys have them written out as field
+// names, so a number key is parsed back out of the name.
+struct FieldName(String);
+
+macro_rules! deserialize_parsed {
+    ($($method:ident $visit:ident $type:ty),*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+                match self.0.parse::<$type>() {
+                    Ok(value) => visitor.$visit(value),
+                    Err(_) => Err(SerdeError::new(format!("Expected a {} key, not \"{}\"", stringify!($type), self.0))),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for FieldName {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_string(self.0)
+    }
+
+    deserialize_parsed! {
+        deserialize_i8 visit_i8 i8, deserialize_i16 visit_i16 i16, deserialize_i32 visit_i32 i32,
+        deserialize_i64 visit_i64 i64, deserialize_u8 visit_u8 u8, deserialize_u16 visit_u16 u16,
+        deserialize_u32 visit_u32 u32, deserialize_u64 visit_u64 u64, deserialize_f32 visit_f32 f32,
+        deserialize_f64 visit_f64 f64
+    }
+
+    forward_to_deserialize_any! {
+        bool i128 u128 char str string bytes byte_buf option unit unit_struct newtype_struct seq
+        tuple tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct VariantAccess {
+    variant: String,
+    value: LoxObject,
+    depth: usize,
+}
+
+impl<'de> de::EnumAccess<'de> for VariantAccess {
+    type Error = SerdeError;
+    type Variant = VariantAccess;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, VariantAccess), SerdeError> {
+        let variant = seed.deserialize(self.variant.as_str().into_deserializer())?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for VariantAccess {
+    type Error = SerdeError;
+
+    fn unit_variant(self) -> Result<(), SerdeError> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, SerdeError> {
+        seed.deserialize(Deserializer::nested(&self.value, self.depth))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, SerdeError> {
+        de::Deserializer::deserialize_any(Deserializer::nested(&self.value, self.depth), visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value, SerdeError> {
+        de::Deserializer::deserialize_any(Deserializer::nested(&self.value, self.depth), visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Lox;
+    use serde::Deserialize;
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Shape {
+        Empty,
+        Circle(f32),
+        Rect(f32, f32),
+        Polygon { name: String, sides: u8 },
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Config {
+        name: String,
+        limit: Option<u32>,
+    }
+
+    fn round_trip<T: Serialize + DeserializeOwned>(value: &T) -> T {
+        from_lox(&to_lox(value).unwrap()).unwrap()
+    }
+
+    fn string(value: &str) -> LoxObject {
+        LoxObject::String(value.to_string())
+    }
+
+    #[test]
+    fn enum_variants_round_trip() {
+        let shapes = vec![
+            Shape::Empty,
+            Shape::Circle(1.5),
+            Shape::Rect(2.0, 3.0),
+            Shape::Polygon { name: "hexagon".to_string(), sides: 6 },
+        ];
+        for shape in &shapes {
+            assert_eq!(&round_trip(shape), shape);
+        }
+
+        assert_eq!(from_lox::<Vec<Shape>>(&to_lox(&shapes).unwrap()), Ok(shapes));
+        assert!(matches!(to_lox(&Shape::Empty), Ok(LoxObject::String(variant)) if variant == "Empty"));
+    }
+
+    #[test]
+    fn missing_and_nil_option_fields_are_none() {
+        let missing = instance("Config", vec![("name".to_string(), string("a"))]);
+        let nil = instance("Config", vec![("name".to_string(), string("b")), ("limit".to_string(), LoxObject::Nil)]);
+        let some = instance("Config", vec![("name".to_string(), string("c")), ("limit".to_string(), LoxObject::Number(3.0))]);
+
+        assert_eq!(from_lox(&missing), Ok(Config { name: "a".to_string(), limit: None }));
+        assert_eq!(from_lox(&nil), Ok(Config { name: "b".to_string(), limit: None }));
+        assert_eq!(from_lox(&some), Ok(Config { name: "c".to_string(), limit: Some(3) }));
+        assert!(from_lox::<Config>(&instance("Config", vec![])).is_err());
+    }
+
+    #[test]
+    fn maps_with_number_keys_round_trip() {
+        let counts: BTreeMap<u32, String> = vec![(1, "one".to_string()), (20, "twenty".to_string())].into_iter().collect();
+        assert_eq!(round_trip(&counts), counts);
+
+        let signs: BTreeMap<i64, bool> = vec![(-3, false), (0, true), (7, true)].into_iter().collect();
+        assert_eq!(round_trip(&signs), signs);
+
+        let names: HashMap<String, f32> = vec![("x".to_string(), 0.5), ("y".to_string(), -2.0)].into_iter().collect();
+        assert_eq!(round_trip(&names), names);
+
+        let bad_key = instance("Map", vec![("-1".to_string(), LoxObject::Boolean(true))]);
+        assert!(from_lox::<BTreeMap<u8, bool>>(&bad_key).is_err());
+    }
+
+    #[test]
+    fn out_of_range_numbers_are_errors() {
+        assert_eq!(from_lox::<u8>(&LoxObject::Number(255.0)), Ok(255));
+        assert!(from_lox::<u8>(&LoxObject::Number(-1.0)).is_err());
+        assert!(from_lox::<u8>(&LoxObject::Number(256.0)).is_err());
+        assert!(from_lox::<i8>(&LoxObject::Number(-129.0)).is_err());
+        assert!(from_lox::<u32>(&LoxObject::Number(1.5)).is_err());
+        assert!(from_lox::<u64>(&LoxObject::Number(1e30)).is_err());
+    }
+
+    #[test]
+    fn value_that_contains_itself_is_an_error() {
+        let mut lox = Lox::new();
+        let value = lox.eval("class Node {} var a = Node(); a.next = a; a").unwrap();
+
+        let error = from_lox::<serde_json::Value>(&value).unwrap_err();
+        assert!(error.message().contains("contains itself"), "{}", error);
+
+        let deep = (0..MAX_DEPTH).fold(LoxObject::Nil, |inner, _| LoxObject::List(Rc::new(vec![inner])));
+        assert!(from_lox::<serde_json::Value>(&deep).is_ok());
+        assert!(from_lox::<serde_json::Value>(&LoxObject::List(Rc::new(vec![deep]))).is_err());
+    }
+
+    #[test]
+    fn functions_and_classes_are_errors() {
+        let mut lox = Lox::new();
+        let values = vec![
+            lox.eval("fun f() {}\nf").unwrap(),
+            lox.eval("clock").unwrap(),
+            lox.eval("class A {}\nA").unwrap(),
+        ];
+
+        for value in values {
+            let error = from_lox::<serde_json::Value>(&value).unwrap_err();
+            assert!(error.message().starts_with("Can't convert a"), "{}", error);
+
+            let list = LoxObject::List(Rc::new(vec![LoxObject::Nil, value]));
+            assert!(from_lox::<Vec<Option<u8>>>(&list).is_err());
+        }
+    }
+}
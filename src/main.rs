@@ -1,60 +1,331 @@
-use crate::scanner::Scanner;
-use std::io::{BufRead, Write};
-use std::{env, fs, io};
-use crate::parser::Parser;
-use crate::interpreter::Interpreter;
-
-mod lib;
-mod expr;
-mod scanner;
-mod parser;
-mod interpreter;
-mod lox_object;
+use rlox::ast_printer::{AstPrinter, PrintStyle};
+use crate::cli::{AstFormat, Command, RunOptions, Source};
+use rlox::scanner::{Literal, ScanError, Scanner, Token};
+use std::io::{BufRead, BufReader, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::{env, fs, io, iter, process, thread};
+use rlox::parser::Parser;
+use rlox::interpreter::Interpreter;
+use rlox::stmt::Stmt;
+
+mod cli;
+mod repl;
+
+// Exit codes, following the BSD sysexits convention
+const EXIT_USAGE: i32 = 64;
+const EXIT_DATA: i32 = 65;
+const EXIT_SOFTWARE: i32 = 70;
+const EXIT_IO: i32 = 74;
+
+// Scripts recurse on the Rust stack, so they run on a thread with room for deep recursion. Only
+// the pages actually used are ever touched.
+const STACK_SIZE: usize = 256 * 1024 * 1024;
 
 fn main() {
+    let result = thread::Builder::new().stack_size(STACK_SIZE).spawn(run_command).map(|thread| thread.join());
+    match result {
+        Ok(Ok(())) => {}
+        // The panic has already been reported
+        Ok(Err(_)) => process::exit(101),
+        Err(e) => fail(&format!("Unable to start the interpreter: {}", e), EXIT_SOFTWARE),
+    }
+}
+
+// A parser that knows how much stack it has
+fn new_parser<I: Iterator<Item = Result<Token, ScanError>>>(tokens: I) -> Parser<I> {
+    let mut parser = Parser::new(tokens);
+    parser.set_stack_size(STACK_SIZE);
+    parser
+}
+
+fn run_command() {
     let args: Vec<String> = env::args().collect();
 
-    match args.len() {
-        1 => run_prompt(),
-        2 => run_file(&args[1]),
-        _ => println!("Usage: rlox [script]"),
+    let command = match cli::parse_args(&args) {
+        Ok(command) => command,
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, cli::USAGE);
+            process::exit(EXIT_USAGE);
+        }
+    };
+
+    match command {
+        Command::Repl if io::stdin().is_terminal() => repl::run(),
+        Command::Repl => run_source(Source::Stdin, vec![], RunOptions::default()),
+        Command::Run { source, args, options } => run_source(source, args, options),
+        Command::RunAstJson { path, args, options } => load_ast_json(&path, &read_file(&path), args, options),
+        Command::Check { source } => check(read_source(&source)),
+        Command::Fmt { source } => fmt(read_source(&source)),
+        Command::Test { paths } => test(&paths),
+        Command::Tokens { source, json } => dump_tokens(read_source(&source), json),
+        Command::Ast { source, format } => dump_ast(read_source(&source), format),
+        Command::Help => println!("{}", cli::USAGE),
+        Command::Version => println!("rlox {}", env!("CARGO_PKG_VERSION")),
+    }
+}
+
+// Scripts are streamed into the scanner rather than read up front
+fn run_source(source: Source, args: Vec<String>, options: RunOptions) {
+    let file = match &source {
+        Source::File(path) => path.clone(),
+        Source::Stdin => "<stdin>".to_string(),
+        Source::Eval(_) => "<eval>".to_string(),
+    };
+
+    let chars: Box<dyn Iterator<Item = char>> = match source {
+        Source::File(path) => match fs::File::open(&path) {
+            Ok(file) => Box::new(source_chars(BufReader::new(file), path)),
+            Err(e) => fail(&format!("Unable to read {}: {}", path, e), EXIT_IO),
+        },
+        Source::Stdin => Box::new(source_chars(io::stdin().lock(), "standard input".to_string())),
+        Source::Eval(code) => Box::new(code.chars().collect::<Vec<char>>().into_iter()),
+    };
+
+    if let Err(code) = run(Scanner::from_chars(chars), &file, args, options) {
+        process::exit(code);
+    }
+}
+
+// The whole source as a string, for the commands that need to look at it more than once
+fn read_source(source: &Source) -> String {
+    match source {
+        Source::File(path) => read_file(path),
+        Source::Stdin => {
+            let mut data = String::new();
+            if let Err(e) = io::Read::read_to_string(&mut io::stdin(), &mut data) {
+                fail(&format!("Unable to read standard input: {}", e), EXIT_IO);
+            }
+            data
+        }
+        Source::Eval(code) => code.clone(),
+    }
+}
+
+fn read_file(path: &str) -> String {
+    match fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(e) => fail(&format!("Unable to read {}: {}", path, e), EXIT_IO),
     }
 }
 
-fn run_prompt() {
-    loop {
-        print!("> ");
-        io::stdout().flush().expect("IO flush failed");
-        let mut line = String::new();
-        let stdin = io::stdin();
-        stdin
-            .lock()
-            .read_line(&mut line)
-            .expect("Could not read line");
+fn fail(message: &str, code: i32) -> ! {
+    eprintln!("{}", message);
+    process::exit(code);
+}
 
-        run(line);
+fn check(source: String) {
+    let mut parser = new_parser(Scanner::new(source));
+    if parser.parse().is_none() {
+        process::exit(EXIT_DATA);
     }
 }
 
-fn run_file(path: &String) {
-    let data = fs::read_to_string(path).expect("Unable to read file");
-    run(data);
+fn fmt(source: String) {
+    let mut parser = new_parser(Scanner::new_lossless(source));
+    let tree = parser.parse_cst();
+
+    // Formatting around a syntax error could change what the program means
+    if parser.had_error() {
+        process::exit(EXIT_DATA);
+    }
+
+    print!("{}", rlox::formatter::format(&tree));
 }
 
-fn run(source: String) {
-    let mut scanner = Scanner::new(source);
-    let tokens = scanner.scan_tokens();
-    let mut parser = Parser::new(tokens.clone());
-    let expression = parser.parse();
-    let interpreter = Interpreter::new();
-    interpreter.interpret(expression.unwrap());
+// Runs each script in its own rlox process and compares what it prints with the
+// `// expect: <output>` comments in the script, in order
+fn test(paths: &[String]) {
+    let mut scripts = vec![];
+    for path in paths {
+        collect_scripts(Path::new(path), &mut scripts);
+    }
+
+    let exe = env::current_exe().expect("Unable to find the rlox executable");
+    let mut failed = 0;
+
+    for script in &scripts {
+        let source = read_file(&script.to_string_lossy());
+        let expected: Vec<&str> = source
+            .lines()
+            .filter_map(|line| line.split("// expect: ").nth(1))
+            .collect();
+
+        let output = process::Command::new(&exe)
+            .arg("run")
+            .arg(script)
+            .output()
+            .expect("Unable to run rlox");
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let actual: Vec<&str> = stdout.lines().collect();
 
-    // For now, just print the tokens
-    /*
-    for token in tokens {
-        println!("{:?}", token);
+        if actual == expected {
+            println!("PASS {}", script.display());
+        } else {
+            failed += 1;
+            println!("FAIL {}", script.display());
+            for i in 0..expected.len().max(actual.len()) {
+                let want = expected.get(i).copied().unwrap_or("<nothing>");
+                let got = actual.get(i).copied().unwrap_or("<nothing>");
+                if want != got {
+                    println!("  line {}: expected {} but got {}", i + 1, want, got);
+                }
+            }
+        }
+    }
+
+    println!("{} passed, {} failed", scripts.len() - failed, failed);
+    if failed > 0 {
+        process::exit(1);
     }
-    */
 }
 
+fn collect_scripts(path: &Path, scripts: &mut Vec<PathBuf>) {
+    if path.is_dir() {
+        let mut entries: Vec<PathBuf> = match fs::read_dir(path) {
+            Ok(entries) => entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).collect(),
+            Err(e) => fail(&format!("Unable to read {}: {}", path.display(), e), EXIT_IO),
+        };
+        entries.sort();
 
+        for entry in entries {
+            if entry.is_dir() || entry.extension().is_some_and(|extension| extension == "lox") {
+                collect_scripts(&entry, scripts);
+            }
+        }
+    } else {
+        scripts.push(path.to_path_buf());
+    }
+}
+
+// One token per line as LINE:COLUMN KIND LEXEME LITERAL, or a JSON array of token objects.
+// Scan errors appear in sequence with the tokens.
+fn dump_tokens(source: String, json: bool) {
+    let mut entries = vec![];
+
+    for result in Scanner::new(source) {
+        let entry = match result {
+            Ok(token) => {
+                let literal = match token.literal() {
+                    Some(Literal::Number(value)) => rlox::ast_json::number(*value),
+                    Some(Literal::String(value)) => serde_json::json!(value),
+                    None => serde_json::Value::Null,
+                };
+
+                if !json {
+                    let position = format!("{}:{}", token.line(), token.column());
+                    let kind = format!("{:?}", token.kind());
+                    let lexeme = format!("{:?}", token.lexeme());
+                    let literal = if literal.is_null() { String::new() } else { literal.to_string() };
+                    let row = format!("{:<8} {:<14} {:<16} {}", position, kind, lexeme, literal);
+                    println!("{}", row.trim_end());
+                }
+
+                serde_json::json!({
+                    "kind": format!("{:?}", token.kind()),
+                    "lexeme": token.lexeme(),
+                    "literal": literal,
+                    "line": token.line(),
+                    "column": token.column(),
+                })
+            }
+            Err(scan_error) => {
+                if !json {
+                    let position = format!("{}:{}", scan_error.line(), scan_error.column());
+                    println!("{:<8} {:<14} {}", position, "Error", scan_error.message());
+                }
+
+                serde_json::json!({
+                    "error": scan_error.message(),
+                    "line": scan_error.line(),
+                    "column": scan_error.column(),
+                })
+            }
+        };
+
+        entries.push(entry);
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&entries).unwrap());
+    }
+}
+
+fn dump_ast(source: String, format: AstFormat) {
+    if format == AstFormat::Cst {
+        let mut parser = new_parser(Scanner::new_lossless(source));
+        print!("{}", parser.parse_cst().dump());
+        return;
+    }
+
+    let mut parser = new_parser(Scanner::new(source));
+    let statements = match parser.parse() {
+        Some(statements) => statements,
+        None => process::exit(EXIT_DATA),
+    };
+
+    match format {
+        AstFormat::Json => println!("{}", serde_json::to_string_pretty(&rlox::ast_json::to_json(&statements)).unwrap()),
+        AstFormat::Source => println!("{}", AstPrinter::new(PrintStyle::Source).print_program(&statements)),
+        _ => println!("{}", AstPrinter::new(PrintStyle::Lisp).print_program(&statements)),
+    }
+}
+
+fn load_ast_json(path: &str, data: &str, args: Vec<String>, options: RunOptions) {
+    let value: serde_json::Value = match serde_json::from_str(data) {
+        Ok(value) => value,
+        Err(e) => fail(&format!("Invalid JSON: {}", e), EXIT_DATA),
+    };
+
+    let statements: Vec<Stmt> = match rlox::ast_json::from_json(&value) {
+        Ok(statements) => statements,
+        Err(e) => fail(&format!("Invalid AST: {}", e.message()), EXIT_DATA),
+    };
+
+    if let Err(code) = interpret(&statements, path, args, options) {
+        process::exit(code);
+    }
+}
+
+// Reads the source a line at a time so the scanner never needs the whole input at once. The
+// whole script is parsed before any of it runs, so failing part way through a read runs nothing.
+fn source_chars<R: BufRead>(mut reader: R, name: String) -> impl Iterator<Item = char> {
+    let mut line = String::new();
+
+    iter::from_fn(move || {
+        line.clear();
+        match reader.read_line(&mut line) {
+            Ok(0) => None,
+            Ok(_) => Some(line.chars().collect::<Vec<char>>()),
+            Err(e) => fail(&format!("Unable to read {}: {}", name, e), EXIT_IO),
+        }
+    })
+    .flatten()
+}
+
+// On failure, returns the exit code for a syntax or runtime error
+fn run<I: Iterator<Item = char>>(scanner: Scanner<I>, file: &str, args: Vec<String>, options: RunOptions) -> Result<(), i32> {
+    let mut parser = new_parser(scanner);
+    let statements = match parser.parse() {
+        Some(statements) => statements,
+        None => return Err(EXIT_DATA),
+    };
+
+    interpret(&statements, file, args, options)
+}
+
+// Runs a parsed script with the limits and permissions from the command line
+fn interpret(statements: &[Stmt], file: &str, args: Vec<String>, options: RunOptions) -> Result<(), i32> {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_file(file);
+    interpreter.set_stack_size(STACK_SIZE);
+    interpreter.set_script_args(args);
+    interpreter.set_max_steps(options.max_steps);
+    interpreter.set_max_allocation(options.max_allocation);
+    interpreter.set_timeout(options.timeout);
+    interpreter.set_permissions(options.permissions);
+    if !interpreter.interpret(statements) {
+        return Err(EXIT_SOFTWARE);
+    }
+
+    Ok(())
+}
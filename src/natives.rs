@@ -0,0 +1,153 @@
+use crate::environment::Environment;
+use crate::interpreter::Interpreter;
+use crate::lox_callable::{NativeFn, NativeFunction};
+use crate::lox_class::{HostMembers, LoxClass, LoxInstance, Method};
+use crate::lox_object::LoxObject;
+use std::cell::RefCell;
+use std::io::{self, BufRead};
+use std::rc::Rc;
+use std::{env, fs, process};
+
+// The functions every program starts with
+pub fn define_globals(globals: &mut Environment) {
+    define(globals, "args", 0, Rc::new(args));
+    define(globals, "at", 2, Rc::new(at));
+    define(globals, "clock", 0, Rc::new(clock));
+    define(globals, "env", 1, Rc::new(env_var));
+    define(globals, "exec", 1, Rc::new(exec));
+    define(globals, "input", 0, Rc::new(input));
+    define(globals, "len", 1, Rc::new(len));
+    define(globals, "num", 1, Rc::new(num));
+    define(globals, "readFile", 1, Rc::new(read_file));
+    define(globals, "str", 1, Rc::new(str));
+    define(globals, "type", 1, Rc::new(type_of));
+    define(globals, "writeFile", 2, Rc::new(write_file));
+}
+
+// The class of the errors the interpreter raises, which scripts can also throw or inherit from.
+// The interpreter adds the line and stack trace when one is thrown.
+pub fn error_class() -> Rc<LoxClass> {
+    let init = |instance: &Rc<RefCell<LoxInstance>>, arguments: Vec<LoxObject>| {
+        instance.borrow_mut().set_field("message", arguments[0].clone());
+        Ok(LoxObject::Nil)
+    };
+
+    let mut members = HostMembers::default();
+    members.methods.insert("init".to_string(), Method::Host { arity: 1, function: Rc::new(init) });
+    Rc::new(LoxClass::new_host("Error".to_string(), members))
+}
+
+fn define(globals: &mut Environment, name: &str, arity: usize, function: Rc<NativeFn>) {
+    globals.define(name, LoxObject::Native(Rc::new(NativeFunction::new(name, arity, function))));
+}
+
+// The arguments given after the script name, as a list of strings
+fn args(interpreter: &mut Interpreter, _arguments: Vec<LoxObject>) -> Result<LoxObject, String> {
+    let args = interpreter.script_args().iter().map(|arg| LoxObject::String(arg.clone())).collect();
+    Ok(LoxObject::List(Rc::new(args)))
+}
+
+// The element of a list, or the character of a string, at a zero-based index
+fn at(_interpreter: &mut Interpreter, arguments: Vec<LoxObject>) -> Result<LoxObject, String> {
+    let index = match &arguments[1] {
+        LoxObject::Number(index) if *index >= 0.0 && index.fract() == 0.0 => *index as usize,
+        other => return Err(format!("at() expects a whole number index, not {}.", other.type_name())),
+    };
+
+    let element = match &arguments[0] {
+        LoxObject::List(elements) => elements.get(index).cloned(),
+        LoxObject::String(value) => value.chars().nth(index).map(|c| LoxObject::String(c.to_string())),
+        other => return Err(format!("at() expects a list or string, not {}.", other.type_name())),
+    };
+
+    element.ok_or_else(|| format!("Index {} is out of range.", index))
+}
+
+// Seconds since the interpreter started. Numbers are single precision, which can't hold the
+// seconds since the epoch to better than a couple of minutes.
+fn clock(interpreter: &mut Interpreter, _arguments: Vec<LoxObject>) -> Result<LoxObject, String> {
+    interpreter.permissions().check_clock()?;
+    Ok(LoxObject::Number(interpreter.start_time().elapsed().as_secs_f32()))
+}
+
+// The value of an environment variable, or nil if it isn't set
+fn env_var(interpreter: &mut Interpreter, arguments: Vec<LoxObject>) -> Result<LoxObject, String> {
+    let name = string_argument("env", &arguments[0])?;
+    interpreter.permissions().check_env(name)?;
+
+    Ok(env::var(name).map_or(LoxObject::Nil, LoxObject::String))
+}
+
+// Runs a program with the whitespace separated arguments after it, without a shell, and returns
+// what it wrote to standard output
+fn exec(interpreter: &mut Interpreter, arguments: Vec<LoxObject>) -> Result<LoxObject, String> {
+    let command = string_argument("exec", &arguments[0])?;
+    let mut words = command.split_whitespace();
+    let program = words.next().ok_or_else(|| "exec() expects a command.".to_string())?;
+    interpreter.permissions().check_spawn(program)?;
+
+    match process::Command::new(program).args(words).output() {
+        Ok(output) => Ok(LoxObject::String(String::from_utf8_lossy(&output.stdout).into_owned())),
+        Err(e) => Err(format!("Unable to run '{}': {}.", program, e)),
+    }
+}
+
+// A line from standard input without its line ending, or nil at the end of input
+fn input(_interpreter: &mut Interpreter, _arguments: Vec<LoxObject>) -> Result<LoxObject, String> {
+    let mut line = String::new();
+
+    match io::stdin().lock().read_line(&mut line) {
+        Ok(0) => Ok(LoxObject::Nil),
+        Ok(_) => Ok(LoxObject::String(line.trim_end_matches(['\n', '\r']).to_string())),
+        Err(e) => Err(format!("Unable to read input: {}.", e)),
+    }
+}
+
+fn len(_interpreter: &mut Interpreter, arguments: Vec<LoxObject>) -> Result<LoxObject, String> {
+    match &arguments[0] {
+        LoxObject::String(value) => Ok(LoxObject::Number(value.chars().count() as f32)),
+        LoxObject::List(elements) => Ok(LoxObject::Number(elements.len() as f32)),
+        other => Err(format!("len() expects a string or list, not {}.", other.type_name())),
+    }
+}
+
+fn num(_interpreter: &mut Interpreter, arguments: Vec<LoxObject>) -> Result<LoxObject, String> {
+    match &arguments[0] {
+        LoxObject::Number(value) => Ok(LoxObject::Number(*value)),
+        LoxObject::String(value) => match value.trim().parse::<f32>() {
+            Ok(number) => Ok(LoxObject::Number(number)),
+            Err(_) => Err(format!("Can't convert \"{}\" to a number.", value)),
+        },
+        other => Err(format!("num() expects a string or number, not {}.", other.type_name())),
+    }
+}
+
+fn read_file(interpreter: &mut Interpreter, arguments: Vec<LoxObject>) -> Result<LoxObject, String> {
+    let path = string_argument("readFile", &arguments[0])?;
+    interpreter.permissions().check_read(path)?;
+
+    fs::read_to_string(path).map(LoxObject::String).map_err(|e| format!("Unable to read '{}': {}.", path, e))
+}
+
+fn str(_interpreter: &mut Interpreter, arguments: Vec<LoxObject>) -> Result<LoxObject, String> {
+    Ok(LoxObject::String(arguments[0].to_string()))
+}
+
+fn type_of(_interpreter: &mut Interpreter, arguments: Vec<LoxObject>) -> Result<LoxObject, String> {
+    Ok(LoxObject::String(arguments[0].type_name().to_string()))
+}
+
+fn write_file(interpreter: &mut Interpreter, arguments: Vec<LoxObject>) -> Result<LoxObject, String> {
+    let path = string_argument("writeFile", &arguments[0])?;
+    let contents = string_argument("writeFile", &arguments[1])?;
+    interpreter.permissions().check_write(path)?;
+
+    fs::write(path, contents).map(|_| LoxObject::Nil).map_err(|e| format!("Unable to write '{}': {}.", path, e))
+}
+
+fn string_argument<'a>(function: &str, argument: &'a LoxObject) -> Result<&'a str, String> {
+    match argument {
+        LoxObject::String(value) => Ok(value),
+        other => Err(format!("{}() expects a string, not {}.", function, other.type_name())),
+    }
+}
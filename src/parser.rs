@@ -1,205 +1,1192 @@
-use crate::expr::{Expression, BooleanLiteral, NilLiteral, GroupingExpr, UnaryExpr, BinaryExpr, NumberLiteral, StringLiteral};
-use crate::scanner::TokenType::*;
-use crate::scanner::{Token, TokenType};
-use crate::lib::report;
+use crate::cst::{Checkpoint, CstBuilder, SyntaxKind, SyntaxNode};
+use crate::expr::{
+    AssignExpr, BinaryExpr, CallExpr, Expr, GetExpr, GroupingExpr, LiteralExpr, LiteralValue, LogicalExpr, SetExpr,
+    SuperExpr, ThisExpr, UnaryExpr, VariableExpr,
+};
+use crate::scanner::TokenKind::*;
+use crate::scanner::{Literal, Position, ScanError, Span, Token, TokenKind};
+use crate::stmt::{
+    BlockStmt, CatchClause, ClassStmt, ExpressionStmt, FunctionStmt, IfStmt, PrintStmt, ReturnStmt, Stmt, ThrowStmt,
+    TryStmt, VarStmt, WhileStmt,
+};
+use crate::{error, report, stack_exhausted, stack_position, stderr_sink, Sink, DEFAULT_STACK_SIZE};
+use std::iter::Peekable;
 use std::rc::Rc;
 
 /*
-expression     → equality ;
-equality       → comparison ( ( "!=" | "==" ) comparison )* ;
-comparison     → term ( ( ">" | ">=" | "<" | "<=" ) term )* ;
-term           → factor ( ( "-" | "+" ) factor )* ;
-factor         → unary ( ( "/" | "*" ) unary )* ;
+program        → declaration* EOF ;
+declaration    → classDecl | funDecl | varDecl | statement ;
+classDecl      → "class" IDENTIFIER ( "<" IDENTIFIER )? "{" function* "}" ;
+funDecl        → "fun" function ;
+function       → IDENTIFIER "(" parameters? ")" block ;
+parameters     → IDENTIFIER ( "," IDENTIFIER )* ;
+varDecl        → "var" IDENTIFIER ( "=" expression )? ";" ;
+statement      → exprStmt | forStmt | ifStmt | printStmt | returnStmt | throwStmt | tryStmt
+               | whileStmt | block ;
+exprStmt       → expression ";" ;
+forStmt        → "for" "(" ( varDecl | exprStmt | ";" ) expression? ";" expression? ")" statement ;
+ifStmt         → "if" "(" expression ")" statement ( "else" statement )? ;
+printStmt      → "print" expression ";" ;
+returnStmt     → "return" expression? ";" ;
+throwStmt      → "throw" expression ";" ;
+tryStmt        → "try" block ( "catch" "(" IDENTIFIER ")" block )? ( "finally" block )? ;
+whileStmt      → "while" "(" expression ")" statement ;
+block          → "{" declaration* "}" ;
+
+Expressions are parsed by precedence climbing (a Pratt parser). Infix and postfix operators are
+not spelled out as grammar rules but looked up in infix_rule below, lowest binding first:
+
+assignment     → ( call "." )? IDENTIFIER "=" assignment      right
+or             → "or"                                         left
+and            → "and"                                        left
+equality       → "!=" | "=="                                  left
+comparison     → ">" | ">=" | "<" | "<="                      left
+term           → "-" | "+"                                    left
+factor         → "/" | "*"                                    left
 unary          → ( "!" | "-" ) unary
-               | primary ;
-primary        → NUMBER | STRING | "true" | "false" | "nil"
-               | "(" expression ")" ;
+call           → "(" arguments? ")" | "." IDENTIFIER          left
+primary        → NUMBER | STRING | "true" | "false" | "nil" | "this"
+               | IDENTIFIER | "(" expression ")" | "super" "." IDENTIFIER ;
  */
 
-pub struct Parser {
-    tokens: Vec<Token>,
-    current: usize,
+const MAX_ARGUMENTS: usize = 255;
+pub const DEFAULT_MAX_DEPTH: usize = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Precedence {
+    Lowest,
+    Assignment,
+    Or,
+    And,
+    Equality,
+    Comparison,
+    Term,
+    Factor,
+    Unary,
+    Call,
+}
+
+impl Precedence {
+    fn next(self) -> Precedence {
+        match self {
+            Precedence::Lowest => Precedence::Assignment,
+            Precedence::Assignment => Precedence::Or,
+            Precedence::Or => Precedence::And,
+            Precedence::And => Precedence::Equality,
+            Precedence::Equality => Precedence::Comparison,
+            Precedence::Comparison => Precedence::Term,
+            Precedence::Term => Precedence::Factor,
+            Precedence::Factor => Precedence::Unary,
+            Precedence::Unary => Precedence::Call,
+            Precedence::Call => Precedence::Call,
+        }
+    }
 }
 
-impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Parser {
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Associativity {
+    Left,
+    Right,
+}
+
+// The precedence table. Every infix operator and how tightly it binds.
+fn infix_rule(kind: TokenKind) -> Option<(Precedence, Associativity)> {
+    match kind {
+        Equal => Some((Precedence::Assignment, Associativity::Right)),
+        Or => Some((Precedence::Or, Associativity::Left)),
+        And => Some((Precedence::And, Associativity::Left)),
+        BangEqual | EqualEqual => Some((Precedence::Equality, Associativity::Left)),
+        Greater | GreaterEqual | Less | LessEqual => Some((Precedence::Comparison, Associativity::Left)),
+        Minus | Plus => Some((Precedence::Term, Associativity::Left)),
+        Slash | Star => Some((Precedence::Factor, Associativity::Left)),
+        LeftParen | Dot => Some((Precedence::Call, Associativity::Left)),
+        _ => None,
+    }
+}
+
+// Raised once a syntax error has been reported, to unwind out of the rule that found it
+#[derive(Debug)]
+pub struct ParseError;
+
+// A line typed at the REPL: statements to run, plus a final expression left without its
+// semicolon whose value should be shown
+pub struct ReplInput {
+    pub statements: Vec<Stmt>,
+    pub expression: Option<Expr>,
+}
+
+// Pulls tokens from the scanner only as far as it needs them, so the whole token stream is never
+// held in memory. Scanner errors are reported as they are reached and the bad token skipped.
+pub struct Parser<I: Iterator<Item = Result<Token, ScanError>>> {
+    tokens: Peekable<I>,
+    previous: Option<Token>,
+    cst: Option<CstBuilder>,
+    had_error: bool,
+    // Set when parsing REPL input, where the last expression statement may omit its semicolon
+    repl: bool,
+    bare_expression: bool,
+    // How many function bodies enclose the current token, for rejecting a top-level return
+    function_depth: usize,
+    // How deeply statements and expressions are nested at the current token. Parsing recurses
+    // at each level, so without a limit deeply nested input would overflow the stack.
+    depth: usize,
+    max_depth: usize,
+    // Where the Rust stack was at the outermost level, and how big it is. Nesting that would use
+    // it up is too deep whatever the limit above.
+    stack_start: usize,
+    stack_size: usize,
+    // Set once input is nested too deeply. The parser doesn't try to recover from that; the rest
+    // of the input is skipped and no more errors are reported.
+    abandoned: bool,
+    diagnostics: Sink,
+}
+
+impl<I: Iterator<Item = Result<Token, ScanError>>> Parser<I> {
+    pub fn new(tokens: I) -> Parser<I> {
         Parser {
-            tokens: tokens,
-            current: 0,
+            tokens: tokens.peekable(),
+            previous: None,
+            cst: None,
+            had_error: false,
+            repl: false,
+            bare_expression: false,
+            function_depth: 0,
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
+            stack_start: 0,
+            stack_size: DEFAULT_STACK_SIZE,
+            abandoned: false,
+            diagnostics: stderr_sink(),
         }
     }
 
-    pub fn parse(&mut self) -> Option<Expression>{
-        Some(self.expression())
+    // Syntax errors are written to standard error unless sent elsewhere
+    pub fn set_diagnostics(&mut self, diagnostics: Sink) {
+        self.diagnostics = diagnostics;
     }
 
-    fn expression(&mut self) -> Expression {
-        self.equality()
+    // How deeply statements and expressions may nest before it is a syntax error
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
     }
 
-    fn equality(&mut self) -> Expression {
-        let mut expr = self.comparison();
+    // How much stack the thread parsing has, 2MB unless set
+    pub fn set_stack_size(&mut self, stack_size: usize) {
+        self.stack_size = stack_size;
+    }
 
-        while self.match_token(&[BangEqual, EqualEqual]) {
-            let operator = (*self.previous()).clone();
-            let right = self.term();
-            expr = Rc::new(BinaryExpr::new(expr, operator, right));
+    // Returns None if any error, scanner or parser, was reported along the way
+    pub fn parse(&mut self) -> Option<Vec<Stmt>> {
+        let mut statements = vec![];
+
+        while !self.is_at_end() {
+            if let Some(statement) = self.declaration() {
+                statements.push(statement);
+            }
         }
 
-        expr
+        if self.had_error {
+            None
+        } else {
+            Some(statements)
+        }
     }
 
-    fn comparison(&mut self) -> Expression  {
-        let mut expr = self.term();
+    pub fn parse_repl(&mut self) -> Option<ReplInput> {
+        self.repl = true;
+        let mut statements = self.parse()?;
 
-        while self.match_token(&[Greater, GreaterEqual, Less, LessEqual]) {
-            let operator = (*self.previous()).clone();
-            let right = self.term();
-            expr = Rc::new(BinaryExpr::new(expr, operator, right));
+        let expression = match statements.pop() {
+            Some(Stmt::Expression(expression_stmt)) if self.bare_expression => Some(expression_stmt.expression),
+            Some(statement) => {
+                statements.push(statement);
+                None
+            }
+            None => None,
+        };
+
+        Some(ReplInput { statements, expression })
+    }
+
+    pub fn had_error(&self) -> bool {
+        self.had_error
+    }
+
+    // Parses the tokens into a concrete syntax tree instead of an abstract one. Given tokens
+    // from a lossless scanner the tree prints back to the original source.
+    pub fn parse_cst(&mut self) -> SyntaxNode {
+        self.cst = Some(CstBuilder::new());
+
+        while !self.is_at_end() {
+            self.declaration();
         }
 
-        expr
+        // Eof carries the trivia at the end of the source
+        let mut cst = self.cst.take().unwrap();
+        for result in self.tokens.by_ref() {
+            match result {
+                Ok(token) => cst.token(token),
+                Err(scan_error) => {
+                    error(&self.diagnostics, scan_error.line(), scan_error.message());
+                    self.had_error = true;
+                }
+            }
+        }
+
+        cst.finish()
     }
 
-    fn term(&mut self) -> Expression {
-        let mut expr = self.factor();
+    // Statements ---------------------------------------------------------------------------------
 
-        while self.match_token(&[Plus, Minus]) {
-            let operator = (*self.previous()).clone();
-            let right = self.factor();
-            expr = Rc::new(BinaryExpr::new(expr, operator, right));
+    fn declaration(&mut self) -> Option<Stmt> {
+        let result = if self.match_token(&[Class]) {
+            self.class_declaration()
+        } else if self.match_token(&[Fun]) {
+            self.function("function").map(Stmt::Function)
+        } else if self.match_token(&[Var]) {
+            self.var_declaration()
+        } else {
+            self.statement()
+        };
+
+        match result {
+            Ok(statement) => Some(statement),
+            Err(_) => {
+                self.synchronize();
+                None
+            }
         }
+    }
+
+    fn class_declaration(&mut self) -> Result<Stmt, ParseError> {
+        let checkpoint = self.checkpoint_before_previous();
+        let start = self.start_of_previous();
+        let name = self.consume_token(Identifier, "Expect class name.")?.clone();
+
+        let superclass = if self.match_token(&[Less]) {
+            let superclass_checkpoint = self.checkpoint();
+            let name = self.consume_token(Identifier, "Expect superclass name.")?.clone();
+            self.wrap_node(superclass_checkpoint, SyntaxKind::Variable);
+            let span = name.span();
+            Some(Expr::Variable(VariableExpr { name, span }))
+        } else {
+            None
+        };
+
+        self.consume_token(LeftBrace, "Expect '{' before class body.")?;
+
+        let mut methods = vec![];
+        while !self.check(RightBrace) && !self.is_at_end() {
+            methods.push(self.function("method")?);
+        }
+
+        self.consume_token(RightBrace, "Expect '}' after class body.")?;
+        self.wrap_node(checkpoint, SyntaxKind::ClassDecl);
 
-        expr
+        Ok(Stmt::Class(ClassStmt { name, superclass, methods, span: self.span_from(start) }))
     }
 
-    fn factor(&mut self) -> Expression {
-        let mut expr = self.unary();
+    // A function declaration or a method. For a declaration the `fun` keyword has been consumed.
+    fn function(&mut self, kind: &str) -> Result<Rc<FunctionStmt>, ParseError> {
+        let (checkpoint, start) = if kind == "method" {
+            (self.checkpoint(), self.start())
+        } else {
+            (self.checkpoint_before_previous(), self.start_of_previous())
+        };
 
-        while self.match_token(&[Slash, Star]) {
-            let operator = (*self.previous()).clone();
-            let right = self.unary();
-            expr = Rc::new(BinaryExpr::new(expr, operator, right));
+        let name = self.consume_token(Identifier, &format!("Expect {} name.", kind))?.clone();
+        self.consume_token(LeftParen, &format!("Expect '(' after {} name.", kind))?;
+
+        let mut params = vec![];
+        if !self.check(RightParen) {
+            loop {
+                if params.len() >= MAX_ARGUMENTS {
+                    let token = self.peek().clone();
+                    self.parser_error(&token, format!("Can't have more than {} parameters.", MAX_ARGUMENTS));
+                }
+
+                params.push(self.consume_token(Identifier, "Expect parameter name.")?.clone());
+
+                if !self.match_token(&[Comma]) {
+                    break;
+                }
+            }
         }
 
-        expr
+        self.consume_token(RightParen, "Expect ')' after parameters.")?;
+        self.consume_token(LeftBrace, &format!("Expect '{{' before {} body.", kind))?;
+        self.function_depth += 1;
+        // A block statement is counted as nesting by statement, a function body here
+        let body = self.nested(Parser::block);
+        self.function_depth -= 1;
+        let body = body?;
+        self.wrap_node(checkpoint, SyntaxKind::FunDecl);
+
+        Ok(Rc::new(FunctionStmt { name, params, body, span: self.span_from(start) }))
     }
 
-    fn unary(&mut self) -> Expression {
-        if self.match_token(&[Bang, Minus]) {
-            let operator = (*self.previous()).clone();
-            let right = self.unary();
-            return Rc::new(UnaryExpr{operator, right});
+    fn var_declaration(&mut self) -> Result<Stmt, ParseError> {
+        let checkpoint = self.checkpoint_before_previous();
+        let start = self.start_of_previous();
+        let name = self.consume_token(Identifier, "Expect variable name.")?.clone();
+
+        let initializer = if self.match_token(&[Equal]) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+
+        self.consume_token(Semicolon, "Expect ';' after variable declaration.")?;
+        self.wrap_node(checkpoint, SyntaxKind::VarDecl);
+
+        Ok(Stmt::Var(VarStmt { name, initializer, span: self.span_from(start) }))
+    }
+
+    fn statement(&mut self) -> Result<Stmt, ParseError> {
+        self.nested(Parser::statement_body)
+    }
+
+    fn statement_body(&mut self) -> Result<Stmt, ParseError> {
+        if self.match_token(&[For]) {
+            return self.for_statement();
         }
 
-        self.primary()
+        if self.match_token(&[If]) {
+            return self.if_statement();
+        }
+
+        if self.match_token(&[Print]) {
+            return self.print_statement();
+        }
+
+        if self.match_token(&[Return]) {
+            return self.return_statement();
+        }
+
+        if self.match_token(&[Throw]) {
+            return self.throw_statement();
+        }
+
+        if self.match_token(&[Try]) {
+            return self.try_statement();
+        }
+
+        if self.match_token(&[While]) {
+            return self.while_statement();
+        }
+
+        if self.match_token(&[LeftBrace]) {
+            let checkpoint = self.checkpoint_before_previous();
+            let start = self.start_of_previous();
+            let statements = self.block()?;
+            self.wrap_node(checkpoint, SyntaxKind::Block);
+            return Ok(Stmt::Block(BlockStmt { statements, span: self.span_from(start) }));
+        }
+
+        self.expression_statement()
     }
 
-    fn primary(&mut self) -> Expression {
+    // There is no for loop in the tree; it becomes the equivalent while loop
+    fn for_statement(&mut self) -> Result<Stmt, ParseError> {
+        let checkpoint = self.checkpoint_before_previous();
+        let start = self.start_of_previous();
+        let keyword = self.previous().clone();
+        self.consume_token(LeftParen, "Expect '(' after 'for'.")?;
+
+        let initializer = if self.match_token(&[Semicolon]) {
+            None
+        } else if self.match_token(&[Var]) {
+            Some(self.var_declaration()?)
+        } else {
+            Some(self.expression_statement()?)
+        };
+
+        let condition = if !self.check(Semicolon) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        self.consume_token(Semicolon, "Expect ';' after loop condition.")?;
+
+        let increment = if !self.check(RightParen) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        self.consume_token(RightParen, "Expect ')' after for clauses.")?;
+
+        let mut body = self.statement()?;
+        self.wrap_node(checkpoint, SyntaxKind::ForStmt);
+        let span = self.span_from(start);
 
-        if self.match_token(&[False]) {
-            return Rc::new(BooleanLiteral{ value: false })
+        if let Some(increment) = increment {
+            body = Stmt::Block(BlockStmt {
+                statements: vec![body, Stmt::Expression(ExpressionStmt { expression: increment, span })],
+                span,
+            });
         }
 
-        if self.match_token(&[True]) {
-            return Rc::new(BooleanLiteral{ value: true })
+        let condition = condition.unwrap_or(Expr::Literal(LiteralExpr { value: LiteralValue::Boolean(true), span }));
+        body = Stmt::While(WhileStmt {
+            keyword,
+            condition,
+            body: Box::new(body),
+            span,
+        });
+
+        if let Some(initializer) = initializer {
+            body = Stmt::Block(BlockStmt {
+                statements: vec![initializer, body],
+                span,
+            });
+        }
+
+        Ok(body)
+    }
+
+    fn if_statement(&mut self) -> Result<Stmt, ParseError> {
+        let checkpoint = self.checkpoint_before_previous();
+        let start = self.start_of_previous();
+        let keyword = self.previous().clone();
+        self.consume_token(LeftParen, "Expect '(' after 'if'.")?;
+        let condition = self.expression()?;
+        self.consume_token(RightParen, "Expect ')' after if condition.")?;
+
+        let then_branch = Box::new(self.statement()?);
+        let else_branch = if self.match_token(&[Else]) {
+            Some(Box::new(self.statement()?))
+        } else {
+            None
+        };
+        self.wrap_node(checkpoint, SyntaxKind::IfStmt);
+
+        Ok(Stmt::If(IfStmt { keyword, condition, then_branch, else_branch, span: self.span_from(start) }))
+    }
+
+    fn print_statement(&mut self) -> Result<Stmt, ParseError> {
+        let checkpoint = self.checkpoint_before_previous();
+        let start = self.start_of_previous();
+        let expression = self.expression()?;
+        self.consume_token(Semicolon, "Expect ';' after value.")?;
+        self.wrap_node(checkpoint, SyntaxKind::PrintStmt);
+
+        Ok(Stmt::Print(PrintStmt { expression, span: self.span_from(start) }))
+    }
+
+    fn return_statement(&mut self) -> Result<Stmt, ParseError> {
+        let checkpoint = self.checkpoint_before_previous();
+        let start = self.start_of_previous();
+        let keyword = self.previous().clone();
+
+        // Reported without unwinding, since the statement itself parses fine
+        if self.function_depth == 0 {
+            self.parser_error(&keyword, "Can't return from top-level code.".to_string());
         }
 
-        if self.match_token(&[Nil]) {
-            return Rc::new(NilLiteral{} )
+        let value = if !self.check(Semicolon) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+
+        self.consume_token(Semicolon, "Expect ';' after return value.")?;
+        self.wrap_node(checkpoint, SyntaxKind::ReturnStmt);
+
+        Ok(Stmt::Return(ReturnStmt { keyword, value, span: self.span_from(start) }))
+    }
+
+    fn throw_statement(&mut self) -> Result<Stmt, ParseError> {
+        let checkpoint = self.checkpoint_before_previous();
+        let start = self.start_of_previous();
+        let keyword = self.previous().clone();
+        let value = self.expression()?;
+        self.consume_token(Semicolon, "Expect ';' after thrown value.")?;
+        self.wrap_node(checkpoint, SyntaxKind::ThrowStmt);
+
+        Ok(Stmt::Throw(ThrowStmt { keyword, value, span: self.span_from(start) }))
+    }
+
+    fn try_statement(&mut self) -> Result<Stmt, ParseError> {
+        let checkpoint = self.checkpoint_before_previous();
+        let start = self.start_of_previous();
+        let body = self.braced_block("try")?;
+
+        let catch = if self.match_token(&[Catch]) {
+            self.consume_token(LeftParen, "Expect '(' after 'catch'.")?;
+            let name = self.consume_token(Identifier, "Expect variable name.")?.clone();
+            self.consume_token(RightParen, "Expect ')' after catch variable.")?;
+            Some(CatchClause { name, body: self.braced_block("catch")? })
+        } else {
+            None
+        };
+
+        let finally = if self.match_token(&[Finally]) {
+            Some(self.braced_block("finally")?)
+        } else {
+            None
+        };
+
+        if catch.is_none() && finally.is_none() {
+            let token = self.peek().clone();
+            return Err(self.parser_error(&token, "Expect 'catch' or 'finally' after try block.".to_string()));
         }
+        self.wrap_node(checkpoint, SyntaxKind::TryStmt);
+
+        Ok(Stmt::Try(TryStmt { body, catch, finally, span: self.span_from(start) }))
+    }
 
-        if self.match_token(&[Number(0f32)]) {
-            let number = (*self.previous()).clone();
+    fn while_statement(&mut self) -> Result<Stmt, ParseError> {
+        let checkpoint = self.checkpoint_before_previous();
+        let start = self.start_of_previous();
+        let keyword = self.previous().clone();
+        self.consume_token(LeftParen, "Expect '(' after 'while'.")?;
+        let condition = self.expression()?;
+        self.consume_token(RightParen, "Expect ')' after condition.")?;
+        let body = Box::new(self.statement()?);
+        self.wrap_node(checkpoint, SyntaxKind::WhileStmt);
 
-           if let Number(value) = number.token_type() {
-                return Rc::new(NumberLiteral{value : value});
+        Ok(Stmt::While(WhileStmt { keyword, condition, body, span: self.span_from(start) }))
+    }
+
+    // The statements of a block whose opening brace has been consumed
+    fn block(&mut self) -> Result<Vec<Stmt>, ParseError> {
+        let mut statements = vec![];
+
+        while !self.check(RightBrace) && !self.is_at_end() {
+            if let Some(statement) = self.declaration() {
+                statements.push(statement);
             }
         }
 
-        if self.match_token(&[StringLiteral("".parse().unwrap())]) {
-            let string = (*self.previous()).clone();
+        self.consume_token(RightBrace, "Expect '}' after block.")?;
+        Ok(statements)
+    }
+
+    // A block that must follow a keyword, as the parts of a try statement do
+    fn braced_block(&mut self, keyword: &str) -> Result<Vec<Stmt>, ParseError> {
+        self.consume_token(LeftBrace, &format!("Expect '{{' after '{}'.", keyword))?;
+        let checkpoint = self.checkpoint_before_previous();
+        let statements = self.block()?;
+        self.wrap_node(checkpoint, SyntaxKind::Block);
+        Ok(statements)
+    }
+
+    fn expression_statement(&mut self) -> Result<Stmt, ParseError> {
+        let checkpoint = self.checkpoint();
+        let start = self.start();
+        let expression = self.expression()?;
+
+        // Only a top-level statement, which is one level deep, may leave out its semicolon
+        if self.repl && self.depth == 1 && self.is_at_end() {
+            self.bare_expression = true;
+        } else {
+            self.consume_token(Semicolon, "Expect ';' after expression.")?;
+        }
+        self.wrap_node(checkpoint, SyntaxKind::ExprStmt);
+
+        Ok(Stmt::Expression(ExpressionStmt { expression, span: self.span_from(start) }))
+    }
+
+    // Expressions --------------------------------------------------------------------------------
 
-            if let StringLiteral(value) = string.token_type() {
-                return Rc::new(StringLiteral{value : value});
+    fn expression(&mut self) -> Result<Expr, ParseError> {
+        self.parse_precedence(Precedence::Lowest)
+    }
+
+    // Parses an expression containing only operators that bind at least as tightly as `min`
+    fn parse_precedence(&mut self, min: Precedence) -> Result<Expr, ParseError> {
+        self.nested(|parser| parser.operators(min))
+    }
+
+    fn operators(&mut self, min: Precedence) -> Result<Expr, ParseError> {
+        let checkpoint = self.checkpoint();
+        let start = self.start();
+        let mut expr = self.unary()?;
+
+        while let Some((precedence, associativity)) = infix_rule(self.peek().kind()) {
+            if precedence < min {
+                break;
             }
+
+            let operator = self.advance().clone();
+
+            expr = match operator.kind() {
+                LeftParen => {
+                    let call = self.finish_call(expr, start)?;
+                    self.wrap_node(checkpoint, SyntaxKind::Call);
+                    call
+                }
+                Dot => {
+                    let name = self.consume_token(Identifier, "Expect property name after '.'.")?.clone();
+                    self.wrap_node(checkpoint, SyntaxKind::Get);
+                    Expr::Get(GetExpr::new(expr, name, self.span_from(start)))
+                }
+                _ => {
+                    let right = match associativity {
+                        Associativity::Left => self.parse_precedence(precedence.next())?,
+                        Associativity::Right => self.parse_precedence(precedence)?,
+                    };
+
+                    match operator.kind() {
+                        Equal => self.assignment(checkpoint, start, expr, &operator, right),
+                        And | Or => {
+                            self.wrap_node(checkpoint, SyntaxKind::Logical);
+                            Expr::Logical(LogicalExpr::new(expr, operator, right, self.span_from(start)))
+                        }
+                        _ => {
+                            self.wrap_node(checkpoint, SyntaxKind::Binary);
+                            Expr::Binary(BinaryExpr::new(expr, operator, right, self.span_from(start)))
+                        }
+                    }
+                }
+            };
         }
 
-        if self.match_token(&[LeftParen]) {
+        Ok(expr)
+    }
 
-            let group = GroupingExpr::new(self.expression());
-            self.consume_token(RightParen, "Expect ')' after expression");
-            return Rc::new(group);
+    // Only a variable or a property can be assigned to. Anything else is reported but, as the
+    // parser is not confused about where it is, parsing carries on.
+    fn assignment(&mut self, checkpoint: Checkpoint, start: Position, target: Expr, equals: &Token, value: Expr) -> Expr {
+        let span = self.span_from(start);
+        match target {
+            Expr::Variable(variable) => {
+                self.wrap_node(checkpoint, SyntaxKind::Assign);
+                Expr::Assign(AssignExpr::new(variable.name, value, span))
+            }
+            Expr::Get(get) => {
+                self.wrap_node(checkpoint, SyntaxKind::Set);
+                Expr::Set(SetExpr::new(*get.object, get.name, value, span))
+            }
+            _ => {
+                self.parser_error(equals, "Invalid assignment target.".to_string());
+                self.wrap_node(checkpoint, SyntaxKind::Assign);
+                target
+            }
+        }
+    }
+
+    fn finish_call(&mut self, callee: Expr, start: Position) -> Result<Expr, ParseError> {
+        let mut arguments = vec![];
+
+        if !self.check(RightParen) {
+            loop {
+                if arguments.len() >= MAX_ARGUMENTS {
+                    let token = self.peek().clone();
+                    self.parser_error(&token, format!("Can't have more than {} arguments.", MAX_ARGUMENTS));
+                }
+
+                arguments.push(self.expression()?);
+
+                if !self.match_token(&[Comma]) {
+                    break;
+                }
+            }
+        }
+
+        let paren = self.consume_token(RightParen, "Expect ')' after arguments.")?.clone();
+        Ok(Expr::Call(CallExpr::new(callee, paren, arguments, self.span_from(start))))
+    }
+
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        let checkpoint = self.checkpoint();
+        let start = self.start();
+
+        if self.match_token(&[Bang, Minus]) {
+            let operator = (*self.previous()).clone();
+            // Calls and property accesses in the operand bind tighter than the operator
+            let right = self.parse_precedence(Precedence::Unary)?;
+            self.wrap_node(checkpoint, SyntaxKind::Unary);
+            return Ok(Expr::Unary(UnaryExpr::new(operator, right, self.span_from(start))));
+        }
+
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, ParseError> {
+        let checkpoint = self.checkpoint();
+        let start = self.start();
+
+        if self.match_token(&[False, True, Nil, Number, StringLiteral]) {
+            self.wrap_node(checkpoint, SyntaxKind::Literal);
+
+            let token = self.previous();
+            let value = match (token.kind(), token.literal()) {
+                (False, _) => LiteralValue::Boolean(false),
+                (True, _) => LiteralValue::Boolean(true),
+                (Nil, _) => LiteralValue::Nil,
+                (_, Some(Literal::Number(value))) => LiteralValue::Number(*value),
+                (_, Some(Literal::String(value))) => LiteralValue::String(value.clone()),
+                (_, None) => panic!("literal token without a value"),
+            };
+            return Ok(Expr::Literal(LiteralExpr { value, span: token.span() }));
+        }
+
+        if self.match_token(&[Super]) {
+            let keyword = self.previous().clone();
+            self.consume_token(Dot, "Expect '.' after 'super'.")?;
+            let method = self.consume_token(Identifier, "Expect superclass method name.")?.clone();
+            self.wrap_node(checkpoint, SyntaxKind::Super);
+            return Ok(Expr::Super(SuperExpr { keyword, method, span: self.span_from(start) }));
+        }
+
+        if self.match_token(&[This]) {
+            self.wrap_node(checkpoint, SyntaxKind::This);
+            let keyword = self.previous().clone();
+            return Ok(Expr::This(ThisExpr { span: keyword.span(), keyword }));
+        }
+
+        if self.match_token(&[Identifier]) {
+            self.wrap_node(checkpoint, SyntaxKind::Variable);
+            let name = self.previous().clone();
+            return Ok(Expr::Variable(VariableExpr { span: name.span(), name }));
+        }
+
+        if self.match_token(&[LeftParen]) {
+            let expression = self.expression()?;
+            self.consume_token(RightParen, "Expect ')' after expression.")?;
+            self.wrap_node(checkpoint, SyntaxKind::Grouping);
+            return Ok(Expr::Grouping(GroupingExpr::new(expression, self.span_from(start))));
         }
 
-        // FIX
         let token = self.peek().clone();
-        self.parser_error(&token, "Expect Expression".to_string());
-        panic!("fix");
+        Err(self.parser_error(&token, "Expect expression.".to_string()))
     }
 
+    // --------------------------------------------------------------------------------------------
 
-    fn match_token(&mut self, types: &[TokenType]) -> bool {
-        for token_type in types {
-            if self.check(token_type.clone()) {
+    fn match_token(&mut self, kinds: &[TokenKind]) -> bool {
+        for &kind in kinds {
+            if self.check(kind) {
                 self.advance();
                 return true;
             }
         }
 
-        return false;
+        false
     }
 
-    fn consume_token(&mut self, token_type: TokenType, message: &str) -> &Token {
-        if self.check(token_type) {
-            return self.advance();
+    fn consume_token(&mut self, kind: TokenKind, message: &str) -> Result<&Token, ParseError> {
+        if self.check(kind) {
+            return Ok(self.advance());
         }
 
-        panic!("Parser error: {}", message);
+        let token = self.peek().clone();
+        Err(self.parser_error(&token, message.to_string()))
     }
 
-    fn check(&mut self, token_type: TokenType) -> bool {
+    fn check(&mut self, kind: TokenKind) -> bool {
         if self.is_at_end() {
             return false;
         }
 
-        let found_type = self.peek().token_type();
-        let answer = found_type == token_type;
-        answer
+        self.peek().kind() == kind
     }
 
     fn advance(&mut self) -> &Token {
         if !self.is_at_end() {
-            self.current = self.current + 1;
+            let token = self.peek().clone();
+            self.tokens.next();
+
+            if let Some(cst) = self.cst.as_mut() {
+                cst.token(token.clone());
+            }
+            self.previous = Some(token);
         }
 
         self.previous()
     }
 
     fn is_at_end(&mut self) -> bool {
-        self.peek().token_type() == Eof
+        self.peek().kind() == Eof
     }
 
     fn peek(&mut self) -> &Token {
-        self.tokens.get(self.current).unwrap()
+        while let Some(Err(scan_error)) = self.tokens.peek() {
+            error(&self.diagnostics, scan_error.line(), scan_error.message());
+            self.had_error = true;
+            self.tokens.next();
+        }
+
+        match self.tokens.peek() {
+            Some(Ok(token)) => token,
+            _ => panic!("Token stream ended without Eof"),
+        }
     }
 
     fn previous(&mut self) -> &Token {
-        self.tokens.get(self.current - 1).unwrap()
+        self.previous.as_ref().unwrap()
+    }
+
+    fn checkpoint(&self) -> Checkpoint {
+        self.cst.as_ref().map_or(0, CstBuilder::checkpoint)
+    }
+
+    // For rules that are only recognized once their first token has been matched
+    fn checkpoint_before_previous(&self) -> Checkpoint {
+        self.checkpoint().saturating_sub(1)
+    }
+
+    // Where the next token starts, for the span of the node it begins
+    fn start(&mut self) -> Position {
+        self.peek().span().start
+    }
+
+    // As start, for a node whose first token has already been matched
+    fn start_of_previous(&mut self) -> Position {
+        self.previous().span().start
+    }
+
+    // From the start of a node to the end of the last token it took
+    fn span_from(&mut self, start: Position) -> Span {
+        Span::new(start, self.previous().span().end)
+    }
+
+    fn wrap_node(&mut self, checkpoint: Checkpoint, kind: SyntaxKind) {
+        if let Some(cst) = self.cst.as_mut() {
+            cst.wrap(checkpoint, kind);
+        }
+    }
+
+    // Parses one level deeper, failing once the input is nested too deeply
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T, ParseError>) -> Result<T, ParseError> {
+        if self.depth == 0 {
+            self.stack_start = stack_position();
+        }
+
+        if self.depth >= self.max_depth || stack_exhausted(self.stack_start, self.stack_size) {
+            let token = self.peek().clone();
+            let parse_error = self.parser_error(&token, "Too much nesting.".to_string());
+            self.abandoned = true;
+            while !self.is_at_end() {
+                self.advance();
+            }
+            return Err(parse_error);
+        }
+
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
     }
 
-    fn parser_error(&mut self, token: &Token, message: String) {
-        if token.token_type() == Eof {
-            report(token.line(), "at end".to_string(), &message);
+    fn parser_error(&mut self, token: &Token, message: String) -> ParseError {
+        self.had_error = true;
+        if self.abandoned {
+            return ParseError;
+        }
+
+        if token.kind() == Eof {
+            report(&self.diagnostics, token.line(), "at end".to_string(), &message);
         } else {
-            report(token.line(),
+            report(&self.diagnostics, token.line(),
                    "at '".to_string() + token.lexeme() + "'" ,
                    &message);
         }
+
+        ParseError
+    }
+
+    // After an error, skips to what is probably the start of the next statement so that parsing
+    // can carry on and report further errors instead of stopping at the first
+    fn synchronize(&mut self) {
+        self.advance();
+
+        while !self.is_at_end() {
+            if self.previous().kind() == Semicolon {
+                return;
+            }
+
+            match self.peek().kind() {
+                Class | Fun | Var | For | If | While | Print | Return | Throw | Try => return,
+                _ => {
+                    self.advance();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast_printer::{AstPrinter, PrintStyle};
+    use crate::scanner::Scanner;
+    use std::cell::RefCell;
+
+    // A xorshift generator, so that a failing case can be reproduced from its seed
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+
+        fn pick<'a>(&mut self, choices: &[&'a str]) -> &'a str {
+            choices[self.below(choices.len())]
+        }
     }
 
-    // TODO
-    fn synchronize() {
-        // In the book an exception is thrown in error recovery so we need an alternative in Rust
+    // The binary operators from loosest to tightest, as in the grammar at the top of the file
+    const BINARY_LEVELS: &[&[TokenKind]] = &[
+        &[Or],
+        &[And],
+        &[BangEqual, EqualEqual],
+        &[Greater, GreaterEqual, Less, LessEqual],
+        &[Minus, Plus],
+        &[Slash, Star],
+    ];
+
+    const BINARY_LEXEMES: &[&[&str]] = &[
+        &["or"],
+        &["and"],
+        &["!=", "=="],
+        &[">", ">=", "<", "<="],
+        &["-", "+"],
+        &["/", "*"],
+    ];
+
+    const NAMES: &[&str] = &["a", "b", "c"];
+
+    // Source for a random expression that the grammar accepts. Depth bounds how far operands
+    // may nest.
+    fn generate_assignment(rng: &mut Rng, depth: usize) -> String {
+        if depth > 0 && rng.below(5) == 0 {
+            let target = if rng.below(2) == 0 {
+                rng.pick(NAMES).to_string()
+            } else {
+                format!("{} . {}", generate_call(rng, depth - 1), rng.pick(NAMES))
+            };
+            return format!("{} = {}", target, generate_assignment(rng, depth - 1));
+        }
+
+        generate_binary(rng, 0, depth)
+    }
+
+    fn generate_binary(rng: &mut Rng, level: usize, depth: usize) -> String {
+        if level == BINARY_LEVELS.len() {
+            return generate_unary(rng, depth);
+        }
+
+        let mut out = generate_binary(rng, level + 1, depth);
+        while depth > 0 && rng.below(5) == 0 {
+            let operator = rng.pick(BINARY_LEXEMES[level]);
+            out = format!("{} {} {}", out, operator, generate_binary(rng, level + 1, depth - 1));
+        }
+        out
+    }
+
+    fn generate_unary(rng: &mut Rng, depth: usize) -> String {
+        if depth > 0 && rng.below(4) == 0 {
+            return format!("{} {}", rng.pick(&["!", "-"]), generate_unary(rng, depth - 1));
+        }
+
+        generate_call(rng, depth)
+    }
+
+    fn generate_call(rng: &mut Rng, depth: usize) -> String {
+        let mut out = generate_primary(rng, depth);
+        while depth > 0 && rng.below(4) == 0 {
+            if rng.below(2) == 0 {
+                let arguments: Vec<String> = (0..rng.below(3)).map(|_| generate_assignment(rng, depth - 1)).collect();
+                out = format!("{} ( {} )", out, arguments.join(" , "));
+            } else {
+                out = format!("{} . {}", out, rng.pick(NAMES));
+            }
+        }
+        out
+    }
+
+    fn generate_primary(rng: &mut Rng, depth: usize) -> String {
+        match rng.below(9) {
+            0 => rng.pick(&["1", "2.5", "0"]).to_string(),
+            1 => "\"s\"".to_string(),
+            2 => rng.pick(&["true", "false", "nil"]).to_string(),
+            3 => "this".to_string(),
+            4 => format!("super . {}", rng.pick(NAMES)),
+            5 | 6 if depth > 0 => format!("( {} )", generate_assignment(rng, depth - 1)),
+            _ => rng.pick(NAMES).to_string(),
+        }
+    }
+
+    // The grammar as a ladder of recursive descent functions, one per precedence level, to check
+    // the table-driven parser against. Only given input the grammar accepts. Spans are left at
+    // their default, as the printed trees leave them out.
+    struct ReferenceParser {
+        tokens: Vec<Token>,
+        current: usize,
+    }
+
+    impl ReferenceParser {
+        fn new(source: &str) -> ReferenceParser {
+            let tokens = Scanner::new(source.to_string()).map(|token| token.expect("scan error")).collect();
+            ReferenceParser { tokens, current: 0 }
+        }
+
+        fn assignment(&mut self) -> Expr {
+            let expr = self.binary(0);
+
+            if self.matches(&[Equal]) {
+                let value = self.assignment();
+                return match expr {
+                    Expr::Variable(variable) => Expr::Assign(AssignExpr::new(variable.name, value, Span::default())),
+                    Expr::Get(get) => Expr::Set(SetExpr::new(*get.object, get.name, value, Span::default())),
+                    _ => panic!("invalid assignment target"),
+                };
+            }
+
+            expr
+        }
+
+        fn binary(&mut self, level: usize) -> Expr {
+            if level == BINARY_LEVELS.len() {
+                return self.unary();
+            }
+
+            let mut expr = self.binary(level + 1);
+            while self.matches(BINARY_LEVELS[level]) {
+                let operator = self.previous();
+                let right = self.binary(level + 1);
+                expr = match operator.kind() {
+                    And | Or => Expr::Logical(LogicalExpr::new(expr, operator, right, Span::default())),
+                    _ => Expr::Binary(BinaryExpr::new(expr, operator, right, Span::default())),
+                };
+            }
+            expr
+        }
+
+        fn unary(&mut self) -> Expr {
+            if self.matches(&[Bang, Minus]) {
+                let operator = self.previous();
+                return Expr::Unary(UnaryExpr::new(operator, self.unary(), Span::default()));
+            }
+
+            self.call()
+        }
+
+        fn call(&mut self) -> Expr {
+            let mut expr = self.primary();
+
+            loop {
+                if self.matches(&[LeftParen]) {
+                    let mut arguments = vec![];
+                    if !self.matches(&[RightParen]) {
+                        loop {
+                            arguments.push(self.assignment());
+                            if !self.matches(&[Comma]) {
+                                break;
+                            }
+                        }
+                        self.expect(RightParen);
+                    }
+                    expr = Expr::Call(CallExpr::new(expr, self.previous(), arguments, Span::default()));
+                } else if self.matches(&[Dot]) {
+                    expr = Expr::Get(GetExpr::new(expr, self.expect(Identifier), Span::default()));
+                } else {
+                    return expr;
+                }
+            }
+        }
+
+        fn primary(&mut self) -> Expr {
+            let token = self.advance();
+
+            let span = Span::default();
+            let literal = |value| Expr::Literal(LiteralExpr { value, span });
+            match token.kind() {
+                False => literal(LiteralValue::Boolean(false)),
+                True => literal(LiteralValue::Boolean(true)),
+                Nil => literal(LiteralValue::Nil),
+                Number | StringLiteral => match token.literal() {
+                    Some(Literal::Number(value)) => literal(LiteralValue::Number(*value)),
+                    Some(Literal::String(value)) => literal(LiteralValue::String(value.clone())),
+                    None => panic!("literal token without a value"),
+                },
+                Super => {
+                    self.expect(Dot);
+                    Expr::Super(SuperExpr { keyword: token, method: self.expect(Identifier), span })
+                }
+                This => Expr::This(ThisExpr { keyword: token, span }),
+                Identifier => Expr::Variable(VariableExpr { name: token, span }),
+                LeftParen => {
+                    let expression = self.assignment();
+                    self.expect(RightParen);
+                    Expr::Grouping(GroupingExpr::new(expression, span))
+                }
+                _ => panic!("unexpected token {:?}", token),
+            }
+        }
+
+        fn matches(&mut self, kinds: &[TokenKind]) -> bool {
+            if kinds.contains(&self.tokens[self.current].kind()) {
+                self.current += 1;
+                return true;
+            }
+            false
+        }
+
+        fn expect(&mut self, kind: TokenKind) -> Token {
+            assert_eq!(self.tokens[self.current].kind(), kind);
+            self.advance()
+        }
+
+        fn advance(&mut self) -> Token {
+            self.current += 1;
+            self.previous()
+        }
+
+        fn previous(&self) -> Token {
+            self.tokens[self.current - 1].clone()
+        }
+    }
+
+    fn lisp(expression: &Expr) -> String {
+        AstPrinter::new(PrintStyle::Lisp).print(expression)
+    }
+
+    fn parse_repl(source: &str) -> Option<ReplInput> {
+        let mut parser = Parser::new(Scanner::new(source.to_string()));
+        parser.set_diagnostics(Rc::new(RefCell::new(Vec::new())));
+        parser.parse_repl()
+    }
+
+    #[test]
+    fn only_a_final_top_level_expression_may_leave_out_its_semicolon() {
+        let input = parse_repl("var a = 1; a + 2").expect("syntax error");
+        assert_eq!(input.statements.len(), 1);
+        assert!(input.expression.is_some());
+
+        let input = parse_repl("a + 2;").expect("syntax error");
+        assert!(input.expression.is_none());
+
+        assert!(parse_repl("if (true) a").is_none());
+        assert!(parse_repl("while (false) a").is_none());
+        assert!(parse_repl("{ a }").is_none());
+        assert!(parse_repl("a b").is_none());
+    }
+
+    #[test]
+    fn pratt_parser_matches_reference_grammar() {
+        for seed in 1..=2000u64 {
+            let mut rng = Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+            let source = generate_assignment(&mut rng, 4);
+
+            let mut reference = ReferenceParser::new(&source);
+            let expected = lisp(&reference.assignment());
+            assert_eq!(reference.tokens[reference.current].kind(), Eof, "reference parser stopped early on {}", source);
+
+            let mut parser = Parser::new(Scanner::new(source.clone()));
+            let actual = parser.expression().map(|expression| lisp(&expression));
+            assert!(!parser.had_error && parser.is_at_end(), "parser rejected {}", source);
+
+            assert_eq!(actual.ok(), Some(expected), "seed {}: {}", seed, source);
+        }
     }
 }
@@ -0,0 +1,222 @@
+use std::env;
+use std::path::{Component, Path, PathBuf};
+
+// What a script may do outside the interpreter. Native functions check these before touching the
+// file system, the environment, the clock or other processes, and fail with a runtime error if the
+// permission hasn't been given. By default a script may only read the clock.
+//
+//   let permissions = Permissions::new().allow_read("./data").allow_env();
+#[derive(Clone, Debug, PartialEq)]
+pub struct Permissions {
+    read: PathGrant,
+    write: PathGrant,
+    env: bool,
+    clock: bool,
+    spawn: bool,
+}
+
+// Either every path, or the given paths and everything under them
+#[derive(Clone, Debug, Default, PartialEq)]
+struct PathGrant {
+    all: bool,
+    paths: Vec<PathBuf>,
+}
+
+impl Permissions {
+
+    pub fn new() -> Permissions {
+        Permissions {
+            read: PathGrant::default(),
+            write: PathGrant::default(),
+            env: false,
+            clock: true,
+            spawn: false,
+        }
+    }
+
+    // Everything, as a script run from a trusted file would expect
+    pub fn all() -> Permissions {
+        Permissions {
+            read: PathGrant { all: true, paths: vec![] },
+            write: PathGrant { all: true, paths: vec![] },
+            env: true,
+            clock: true,
+            spawn: true,
+        }
+    }
+
+    // Reading the file, or anything under the directory, at this path
+    pub fn allow_read<P: AsRef<Path>>(mut self, path: P) -> Permissions {
+        self.read.paths.push(absolute(path.as_ref()));
+        self
+    }
+
+    pub fn allow_read_all(mut self) -> Permissions {
+        self.read.all = true;
+        self
+    }
+
+    pub fn allow_write<P: AsRef<Path>>(mut self, path: P) -> Permissions {
+        self.write.paths.push(absolute(path.as_ref()));
+        self
+    }
+
+    pub fn allow_write_all(mut self) -> Permissions {
+        self.write.all = true;
+        self
+    }
+
+    pub fn allow_env(mut self) -> Permissions {
+        self.env = true;
+        self
+    }
+
+    pub fn allow_spawn(mut self) -> Permissions {
+        self.spawn = true;
+        self
+    }
+
+    pub fn deny_clock(mut self) -> Permissions {
+        self.clock = false;
+        self
+    }
+
+    pub fn check_read(&self, path: &str) -> Result<(), String> {
+        check(self.read.allows(path), || format!("Reading '{}' is not permitted.", path))
+    }
+
+    pub fn check_write(&self, path: &str) -> Result<(), String> {
+        check(self.write.allows(path), || format!("Writing '{}' is not permitted.", path))
+    }
+
+    pub fn check_env(&self, name: &str) -> Result<(), String> {
+        check(self.env, || format!("Reading the environment variable '{}' is not permitted.", name))
+    }
+
+    pub fn check_clock(&self) -> Result<(), String> {
+        check(self.clock, || "Reading the clock is not permitted.".to_string())
+    }
+
+    pub fn check_spawn(&self, program: &str) -> Result<(), String> {
+        check(self.spawn, || format!("Running '{}' is not permitted.", program))
+    }
+}
+
+impl Default for Permissions {
+
+    fn default() -> Permissions {
+        Permissions::new()
+    }
+}
+
+impl PathGrant {
+
+    fn allows(&self, path: &str) -> bool {
+        self.all || {
+            let path = absolute(Path::new(path));
+            self.paths.iter().any(|allowed| path.starts_with(allowed))
+        }
+    }
+}
+
+fn check(allowed: bool, message: impl FnOnce() -> String) -> Result<(), String> {
+    if allowed {
+        Ok(())
+    } else {
+        Err(message())
+    }
+}
+
+// The path from the root, with symbolic links followed as far as the path exists so a link can't
+// lead out of an allowed directory, and `.` and `..` resolved so neither can a path like
+// `data/../secret`
+fn absolute(path: &Path) -> PathBuf {
+    let path = env::current_dir().map(|dir| dir.join(path)).unwrap_or_else(|_| path.to_path_buf());
+
+    let mut resolved = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            _ => resolved.push(component),
+        }
+
+        if let Ok(canonical) = resolved.canonicalize() {
+            resolved = canonical;
+        }
+    }
+
+    resolved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, process};
+
+    // A fresh directory holding `data/`, `database/` and a `secret` file beside them
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("rlox-permissions-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("data")).unwrap();
+        fs::create_dir_all(dir.join("database")).unwrap();
+        fs::write(dir.join("data").join("notes.txt"), "notes").unwrap();
+        fs::write(dir.join("database").join("rows.txt"), "rows").unwrap();
+        fs::write(dir.join("secret"), "secret").unwrap();
+        dir
+    }
+
+    fn path(dir: &Path, relative: &str) -> String {
+        dir.join(relative).to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn parent_directory_cannot_leave_an_allowed_directory() {
+        let dir = scratch_dir("parent");
+        let permissions = Permissions::new().allow_read(dir.join("data"));
+
+        assert!(permissions.check_read(&path(&dir, "data/notes.txt")).is_ok());
+        assert!(permissions.check_read(&path(&dir, "data/../secret")).is_err());
+        assert!(permissions.check_read(&path(&dir, "data/./../data/notes.txt")).is_ok());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symbolic_link_cannot_leave_an_allowed_directory() {
+        let dir = scratch_dir("symlink");
+        std::os::unix::fs::symlink(dir.join("secret"), dir.join("data").join("link")).unwrap();
+        std::os::unix::fs::symlink(&dir, dir.join("data").join("up")).unwrap();
+        let permissions = Permissions::new().allow_read(dir.join("data"));
+
+        assert!(permissions.check_read(&path(&dir, "data/link")).is_err());
+        assert!(permissions.check_read(&path(&dir, "data/up/secret")).is_err());
+        assert!(permissions.check_read(&path(&dir, "data/up/data/notes.txt")).is_ok());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_path_under_an_allowed_directory_is_allowed() {
+        let dir = scratch_dir("missing");
+        let permissions = Permissions::new().allow_write(dir.join("data"));
+
+        assert!(permissions.check_write(&path(&dir, "data/new.txt")).is_ok());
+        assert!(permissions.check_write(&path(&dir, "data/new/deeper.txt")).is_ok());
+        assert!(permissions.check_write(&path(&dir, "data/new/../../secret")).is_err());
+        assert!(permissions.check_write(&path(&dir, "elsewhere/new.txt")).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn sibling_sharing_a_prefix_is_not_allowed() {
+        let dir = scratch_dir("prefix");
+        let permissions = Permissions::new().allow_read(dir.join("data"));
+
+        assert!(permissions.check_read(&path(&dir, "data")).is_ok());
+        assert!(permissions.check_read(&path(&dir, "database/rows.txt")).is_err());
+        assert!(permissions.check_read(&path(&dir, "database")).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,315 @@
+use rlox::ast_printer::{AstPrinter, PrintStyle};
+use rlox::expr::Expr;
+use rlox::interpreter::{InterruptHandle, Interpreter};
+use rlox::lox_object::LoxObject;
+use rlox::scanner::{Scanner, TokenKind, KEYWORDS};
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::FileHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use std::path::PathBuf;
+use std::time::Instant;
+use std::{env, fs};
+
+// The interactive prompt. Lines are edited with the usual readline keys and remembered across
+// sessions in ~/.rlox_history. Tab completes keywords and global names, and the fields and
+// methods of an instance after a dot. Input with unclosed braces or parentheses, or an unterminated
+// string, continues on the next line under a `...` prompt. Ctrl-C abandons the current input, or
+// stops the code running, and Ctrl-D on an empty line exits.
+//
+// One interpreter lives for the whole session, so definitions carry over from entry to entry.
+// An entry ending in an expression without a semicolon shows that expression's value.
+//
+// A line starting with a colon is a command to the prompt itself rather than Lox code.
+
+const PROMPT: &str = "> ";
+const CONTINUATION_PROMPT: &str = "... ";
+const HISTORY_FILE: &str = ".rlox_history";
+
+const COMMANDS: &str = "\
+:load <file>    Run a script in this session
+:reset          Forget everything defined so far
+:env            List the global variables, functions and classes
+:type <expr>    Show the type of an expression's value
+:ast <code>     Show the syntax tree of some code
+:tokens <code>  Show the tokens of some code
+:time <code>    Run some code and show how long it took
+:quit           Leave the prompt
+:help           Show this list";
+
+pub fn run() {
+    let mut editor: Editor<LoxHelper, FileHistory> = match Editor::new() {
+        Ok(editor) => editor,
+        Err(e) => {
+            eprintln!("Unable to start the prompt: {}", e);
+            return;
+        }
+    };
+
+    editor.set_helper(Some(LoxHelper { globals: vec![] }));
+
+    let history = history_path();
+    if let Some(history) = &history {
+        // There is no history yet on the first run
+        let _ = editor.load_history(history);
+    }
+
+    // While the prompt waits for input the terminal hands it Ctrl-C as a key. While code runs,
+    // Ctrl-C is a signal, which stops the code instead of the process.
+    let interrupt = InterruptHandle::new();
+    let handler = interrupt.clone();
+    if let Err(e) = ctrlc::set_handler(move || handler.interrupt()) {
+        eprintln!("Ctrl-C will not stop running code: {}", e);
+    }
+
+    let mut interpreter = new_interpreter(interrupt);
+    let mut input = String::new();
+
+    loop {
+        let prompt = if input.is_empty() { PROMPT } else { CONTINUATION_PROMPT };
+
+        if let Some(helper) = editor.helper_mut() {
+            helper.globals = interpreter.globals();
+        }
+        interpreter.reset_usage();
+
+        match editor.readline(prompt) {
+            Ok(line) if input.is_empty() && line.trim_start().starts_with(':') => {
+                let _ = editor.add_history_entry(line.trim());
+                if !command(&mut interpreter, line.trim()) {
+                    break;
+                }
+            }
+            Ok(line) => {
+                input.push_str(&line);
+                input.push('\n');
+
+                if is_incomplete(&input) {
+                    continue;
+                }
+
+                let _ = editor.add_history_entry(input.trim_end());
+                eval(&mut interpreter, &input);
+                input.clear();
+            }
+            Err(ReadlineError::Interrupted) => input.clear(),
+            Err(ReadlineError::Eof) => break,
+            Err(e) => {
+                eprintln!("Unable to read input: {}", e);
+                break;
+            }
+        }
+    }
+
+    if let Some(history) = &history {
+        if let Err(e) = editor.save_history(history) {
+            eprintln!("Unable to save history to {}: {}", history.display(), e);
+        }
+    }
+}
+
+fn new_interpreter(interrupt: InterruptHandle) -> Interpreter {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_file("<repl>");
+    interpreter.set_interrupt_handle(interrupt);
+    interpreter.set_stack_size(crate::STACK_SIZE);
+    interpreter
+}
+
+// Completion works from a copy of the globals taken before each line is read, since the
+// interpreter is not running while the user types
+struct LoxHelper {
+    globals: Vec<(String, LoxObject)>,
+}
+
+impl LoxHelper {
+    // What the dotted names before a property access refer to, e.g. `a.b` in `a.b.c`
+    fn resolve(&self, path: &str) -> Option<LoxObject> {
+        let mut names = path.split('.');
+        let first = names.next()?;
+        let mut object = self.globals.iter().find(|(name, _)| name == first)?.1.clone();
+
+        for name in names {
+            object = match object {
+                LoxObject::Instance(instance) => instance.borrow().field(name)?,
+                _ => return None,
+            };
+        }
+
+        Some(object)
+    }
+
+    fn candidates(&self, receiver: Option<&str>) -> Vec<String> {
+        match receiver {
+            Some(path) => match self.resolve(path) {
+                Some(LoxObject::Instance(instance)) => {
+                    let instance = instance.borrow();
+                    let mut names = instance.field_names();
+                    names.extend(instance.class().method_names());
+                    names
+                }
+                _ => vec![],
+            },
+            None => {
+                let keywords = KEYWORDS.iter().map(|(keyword, _)| keyword.to_string());
+                keywords.chain(self.globals.iter().map(|(name, _)| name.clone())).collect()
+            }
+        }
+    }
+}
+
+impl Completer for LoxHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        let before = &line[..pos];
+        let is_name = |c: char| c.is_ascii_alphanumeric() || c == '_';
+
+        let start = before.trim_end_matches(is_name).len();
+        let prefix = &before[start..];
+
+        let receiver = match before[..start].strip_suffix('.') {
+            Some(rest) => {
+                let path_start = rest.trim_end_matches(|c: char| is_name(c) || c == '.').len();
+                Some(&rest[path_start..])
+            }
+            None => None,
+        };
+
+        let mut candidates: Vec<String> = self
+            .candidates(receiver)
+            .into_iter()
+            .filter(|candidate| candidate.starts_with(prefix))
+            .collect();
+        candidates.sort();
+        candidates.dedup();
+
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for LoxHelper {
+    type Hint = String;
+}
+
+impl Highlighter for LoxHelper {}
+
+impl Validator for LoxHelper {}
+
+impl Helper for LoxHelper {}
+
+fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE))
+}
+
+// True while the input so far can't be a whole entry: more opening than closing braces or
+// parentheses, or a string still open at the end
+fn is_incomplete(source: &str) -> bool {
+    let mut depth: i32 = 0;
+
+    for result in Scanner::new(source.to_string()) {
+        match result {
+            Ok(token) => match token.kind() {
+                TokenKind::LeftParen | TokenKind::LeftBrace => depth += 1,
+                TokenKind::RightParen | TokenKind::RightBrace => depth -= 1,
+                _ => {}
+            },
+            Err(scan_error) if scan_error.message() == "Unterminated string." => return true,
+            Err(_) => {}
+        }
+    }
+
+    depth > 0
+}
+
+// Runs a colon command. Returns false when the session should end.
+fn command(interpreter: &mut Interpreter, line: &str) -> bool {
+    let (name, argument) = match line.find(char::is_whitespace) {
+        Some(index) => (&line[..index], line[index..].trim()),
+        None => (line, ""),
+    };
+
+    match (name, argument) {
+        (":quit" | ":q", _) => return false,
+        (":help", _) => println!("{}", COMMANDS),
+        (":reset", _) => {
+            *interpreter = new_interpreter(interpreter.interrupt_handle());
+        }
+        (":env", _) => {
+            for (name, value) in interpreter.globals() {
+                println!("{} = {}", name, value);
+            }
+        }
+        (":load", path) if !path.is_empty() => match fs::read_to_string(path) {
+            Ok(source) => {
+                if let Some(statements) = crate::new_parser(Scanner::new(source)).parse() {
+                    interpreter.set_file(path);
+                    interpreter.interpret(&statements);
+                    interpreter.set_file("<repl>");
+                }
+            }
+            Err(e) => eprintln!("Unable to read {}: {}", path, e),
+        },
+        (":type", code) if !code.is_empty() => {
+            if let Some(expression) = expression(code) {
+                if let Some(value) = interpreter.interpret_expression(&expression) {
+                    println!("{}", value.type_name());
+                }
+            }
+        }
+        (":ast", code) if !code.is_empty() => {
+            if let Some(input) = crate::new_parser(Scanner::new(code.to_string())).parse_repl() {
+                let mut printer = AstPrinter::new(PrintStyle::Lisp);
+                if !input.statements.is_empty() {
+                    println!("{}", printer.print_program(&input.statements));
+                }
+                if let Some(expression) = input.expression {
+                    println!("{}", printer.print(&expression));
+                }
+            }
+        }
+        (":tokens", code) if !code.is_empty() => crate::dump_tokens(code.to_string(), false),
+        (":time", code) if !code.is_empty() => {
+            let start = Instant::now();
+            eval(interpreter, code);
+            println!("Elapsed: {:?}", start.elapsed());
+        }
+        (":load" | ":type" | ":ast" | ":tokens" | ":time", _) => eprintln!("{} needs an argument", name),
+        _ => eprintln!("Unknown command {}\n\n{}", name, COMMANDS),
+    }
+
+    true
+}
+
+// The code as a single expression, with no statements before it
+fn expression(code: &str) -> Option<Expr> {
+    let input = crate::new_parser(Scanner::new(code.to_string())).parse_repl()?;
+
+    match input.expression {
+        Some(expression) if input.statements.is_empty() => Some(expression),
+        _ => {
+            eprintln!("Expected an expression without a trailing semicolon");
+            None
+        }
+    }
+}
+
+fn eval(interpreter: &mut Interpreter, source: &str) {
+    let input = match crate::new_parser(Scanner::new(source.to_string())).parse_repl() {
+        Some(input) => input,
+        None => return,
+    };
+
+    if !interpreter.interpret(&input.statements) {
+        return;
+    }
+
+    if let Some(expression) = input.expression {
+        if let Some(value) = interpreter.interpret_expression(&expression) {
+            println!("{}", value);
+        }
+    }
+}
@@ -1,11 +1,11 @@
-use crate::scanner::TokenType::*;
-use crate::lib::error;
+use crate::scanner::TokenKind::*;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::vec;
 
 
-#[derive(Clone, Debug)]
-pub enum TokenType {
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TokenKind {
     // Single-character tokens.
     LeftParen,
     RightParen,
@@ -30,15 +30,17 @@ pub enum TokenType {
     LessEqual,
 
     // Literals.
-    Identifier(String),
-    StringLiteral(String),
-    Number(f32),
+    Identifier,
+    StringLiteral,
+    Number,
 
     // Keywords.
     And,
+    Catch,
     Class,
     Else,
     False,
+    Finally,
     Fun,
     For,
     If,
@@ -48,7 +50,9 @@ pub enum TokenType {
     Return,
     Super,
     This,
+    Throw,
     True,
+    Try,
     Var,
     While,
 
@@ -56,304 +60,218 @@ pub enum TokenType {
 }
 
 
-impl PartialEq for TokenType {
-    fn eq(&self, other: &Self) -> bool {
+// Value carried by literal tokens, kept apart from the kind so kinds stay cheap to compare
+#[derive(Clone, Debug, PartialEq)]
+pub enum Literal {
+    String(String),
+    Number(f32),
+}
 
-        //let mut result = false;
+#[derive(Clone, Debug, PartialEq)]
+pub enum TriviaKind {
+    Whitespace,
+    Comment,
+    // Characters the scanner could not make a token from. Kept so the source still round-trips.
+    Unknown,
+}
 
-        // A TokenType matches another if it is the same variant regardless of whether
-        // the data associated with the variont is the same
+// Source text that carries no meaning for the parser but is needed to reproduce the input exactly
+#[derive(Clone, Debug)]
+pub struct Trivia {
+    kind: TriviaKind,
+    text: String,
+}
 
-        if let lhs = self {
-            if let rhs = other {
-                println!("{:?} == {:?}", lhs, rhs);
-            }
-        }
-        let result = match self {
-
-            TokenType::Identifier(_) => match other  {
-                TokenType::Identifier(_) =>  true,
-                _ =>  false,
-            },
-            TokenType::StringLiteral(_) => match other  {
-                TokenType::StringLiteral(_) =>  true,
-                _ =>  false,
-            },
-            TokenType::Number(_) => match other  {
-                TokenType::Number(_) =>  true,
-                _ =>  false,
-            },
-
-            TokenType::LeftParen => match other {
-            TokenType::LeftParen =>  true,
-            _ =>  false,
-            },
-
-            TokenType::RightParen => match other {
-                TokenType::RightParen =>  true,
-                _ =>  false,
-            },
-
-            TokenType::LeftBrace => match other {
-                TokenType::LeftBrace =>  true,
-                _ =>  false,
-            },
-
-            TokenType::RightBrace => match other {
-                TokenType::RightBrace =>  true,
-                _ =>  false,
-            },
-
-            TokenType::Comma => match other {
-                TokenType::Comma =>  true,
-                _ =>  false,
-            },
-
-            TokenType::Dot => match other {
-                TokenType::Dot =>  true,
-                _ =>  false,
-            },
-
-            TokenType::Minus => match other {
-                TokenType::Minus =>  true,
-                _ =>  false,
-            },
-
-            TokenType::Plus => match other {
-                TokenType::Plus =>  true,
-                _ =>  false,
-            },
-
-            TokenType::Semicolon => match other {
-                TokenType::Semicolon =>  true,
-                _ =>  false,
-            },
-
-            TokenType::Slash => match other {
-                TokenType::Slash =>  true,
-                _ =>  false,
-            },
-
-            TokenType::Star => match other {
-                TokenType::Star =>  true,
-                _ =>  false,
-            },
-
-            TokenType::Bang => match other {
-                TokenType::Bang =>  true,
-                _ =>  false,
-            },
-
-
-            TokenType::BangEqual => match other {
-                TokenType::BangEqual =>  true,
-                _ =>  false,
-            },
-
-            TokenType::Equal => match other {
-                TokenType::Equal =>  true,
-                _ =>  false,
-            },
-
-            TokenType::EqualEqual => match other {
-                TokenType::EqualEqual =>  true,
-                _ =>  false,
-            },
-
-            TokenType::Greater => match other {
-                TokenType::Greater =>  true,
-                _ =>  false,
-            },
-
-            TokenType::GreaterEqual => match other {
-                TokenType::GreaterEqual =>  true,
-                _ =>  false,
-            },
-
-            TokenType::Less => match other {
-                TokenType::Less =>  true,
-                _ =>  false,
-            },
-
-            TokenType::LessEqual => match other {
-                TokenType::LessEqual =>  true,
-                _ =>  false,
-            },
-
-            TokenType::And => match other {
-                TokenType::And =>  true,
-                _ =>  false,
-            },
-
-            TokenType::Class => match other {
-                TokenType::Class =>  true,
-                _ =>  false,
-            },
-
-            TokenType::Else => match other {
-                TokenType::Else =>  true,
-                _ =>  false,
-            },
-
-            TokenType::False => match other {
-                TokenType::False =>  true,
-                _ =>  false,
-            },
-
-            TokenType::Fun => match other {
-                TokenType::Fun =>  true,
-                _ =>  false,
-            },
-
-            TokenType::For => match other {
-                TokenType::For =>  true,
-                _ =>  false,
-            },
-
-            TokenType::If => match other {
-                TokenType::If =>  true,
-                _ =>  false,
-            },
-
-            TokenType::If => match other {
-                TokenType::If =>  true,
-                _ =>  false,
-            },
-
-            TokenType::Nil => match other {
-                TokenType::Nil =>  true,
-                _ =>  false,
-            },
-
-            TokenType::Or => match other {
-                TokenType::Or =>  true,
-                _ =>  false,
-            },
-
-            TokenType::Print => match other {
-                TokenType::Print =>  true,
-                _ =>  false,
-            },
-
-            TokenType::Return => match other {
-                TokenType::Return =>  true,
-                _ =>  false,
-            },
-
-            TokenType::Super => match other {
-                TokenType::Super =>  true,
-                _ =>  false,
-            },
-
-            TokenType::This => match other {
-                TokenType::This =>  true,
-                _ =>  false,
-            },
-
-            TokenType::True => match other {
-                TokenType::True =>  true,
-                _ =>  false,
-            },
-
-            TokenType::Var => match other {
-                TokenType::Var =>  true,
-                _ =>  false,
-            },
-
-            TokenType::While => match other {
-                TokenType::While =>  true,
-                _ =>  false,
-            },
-
-            TokenType::Eof => match other {
-                TokenType::Eof =>  true,
-                _ =>  false,
-            },
+impl Trivia {
+    pub fn kind(&self) -> TriviaKind {
+        self.kind.clone()
+    }
 
-        };
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
 
+// A place in the source. Lines and columns count from 1.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
 
-        println!("returning: {}", result);
-        return result;
-   }
+// The source a syntax tree node was parsed from. The end is just past its last character.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
 }
 
+impl Span {
+    pub fn new(start: Position, end: Position) -> Span {
+        Span { start, end }
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct Token {
-    token_type: TokenType,
+    kind: TokenKind,
     lexeme: String,
+    literal: Option<Literal>,
     line: usize,
+    column: usize,
+    leading_trivia: Vec<Trivia>,
 }
 
 impl Token {
-    pub fn token_type(&self) -> TokenType {
-        self.token_type.clone()
+    pub fn new(kind: TokenKind, lexeme: String, literal: Option<Literal>, line: usize, column: usize) -> Token {
+        Token {
+            kind,
+            lexeme,
+            literal,
+            line,
+            column,
+            leading_trivia: vec![],
+        }
+    }
+
+    pub fn kind(&self) -> TokenKind {
+        self.kind
     }
 
     pub fn lexeme(&self) -> &str {
         &self.lexeme
     }
+
+    pub fn literal(&self) -> Option<&Literal> {
+        self.literal.as_ref()
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    // A string literal can run over several lines, so the end is found by walking the lexeme
+    pub fn span(&self) -> Span {
+        let start = Position { line: self.line, column: self.column };
+        let mut end = start;
+        for c in self.lexeme.chars() {
+            if c == '\n' {
+                end.line += 1;
+                end.column = 1;
+            } else {
+                end.column += 1;
+            }
+        }
+        Span::new(start, end)
+    }
+
+    // Whitespace and comments preceding the token. Only populated by a lossless scanner.
+    pub fn leading_trivia(&self) -> &[Trivia] {
+        &self.leading_trivia
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ScanError {
+    line: usize,
+    column: usize,
+    message: String,
+}
+
+impl ScanError {
     pub fn line(&self) -> usize {
         self.line
     }
+
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
 }
 
-pub struct Scanner {
-    source: String,
-    tokens: Vec<Token>,
-    keywords: HashMap<String, TokenType>,
-    current: usize,
-    start: usize,
+// The reserved words, which scan as their own token kinds rather than as identifiers
+pub const KEYWORDS: &[(&str, TokenKind)] = &[
+    ("and", And),
+    ("catch", Catch),
+    ("class", Class),
+    ("else", Else),
+    ("false", False),
+    ("finally", Finally),
+    ("for", For),
+    ("fun", Fun),
+    ("if", If),
+    ("nil", Nil),
+    ("or", Or),
+    ("print", Print),
+    ("return", Return),
+    ("super", Super),
+    ("this", This),
+    ("throw", Throw),
+    ("true", True),
+    ("try", Try),
+    ("var", Var),
+    ("while", While),
+];
+
+// Produces tokens on demand from a stream of characters. Iterating yields every token in turn,
+// ending with Eof, with errors reported in place of the offending characters.
+pub struct Scanner<I: Iterator<Item = char> = vec::IntoIter<char>> {
+    source: I,
+    lookahead: VecDeque<char>,
+    lexeme: String,
+    keywords: HashMap<String, TokenKind>,
+    // Position of the next character, and of the first character of the current lexeme
     line: usize,
+    column: usize,
+    start_line: usize,
+    start_column: usize,
+    lossless: bool,
+    trivia: Vec<Trivia>,
+    finished: bool,
 }
 
 impl Scanner {
     pub fn new(source: String) -> Scanner {
-        let mut keywords = HashMap::new();
-        keywords.insert("and".to_string(), And);
-        keywords.insert("class".to_string(), Class);
-        keywords.insert("else".to_string(), Else);
-        keywords.insert("false".to_string(), False);
-        keywords.insert("for".to_string(), For);
-        keywords.insert("fun".to_string(), Fun);
-        keywords.insert("if".to_string(), If);
-        keywords.insert("nil".to_string(), Nil);
-        keywords.insert("or".to_string(), Or);
-        keywords.insert("print".to_string(), Print);
-        keywords.insert("return".to_string(), Return);
-        keywords.insert("super".to_string(), Super);
-        keywords.insert("this".to_string(), This);
-        keywords.insert("true".to_string(), True);
-        keywords.insert("var".to_string(), Var);
-        keywords.insert("while".to_string(), While);
+        Scanner::from_chars(source.chars().collect::<Vec<char>>().into_iter())
+    }
+
+    // A scanner that keeps whitespace and comments as trivia on the following token, so that
+    // concatenating every token's trivia and lexeme reproduces the source exactly
+    pub fn new_lossless(source: String) -> Scanner {
+        let mut scanner = Scanner::new(source);
+        scanner.lossless = true;
+        scanner
+    }
+}
+
+impl<I: Iterator<Item = char>> Scanner<I> {
+    pub fn from_chars(source: I) -> Scanner<I> {
+        let keywords = KEYWORDS.iter().map(|(name, kind)| (name.to_string(), *kind)).collect();
 
         Scanner {
             source,
-            tokens: vec![],
+            lookahead: VecDeque::new(),
+            lexeme: String::new(),
             keywords,
-            current: 0,
-            start: 0,
-            line: 0,
-        }
-    }
-
-    pub fn scan_tokens(&mut self) -> Vec<Token> {
-        while !self.is_at_end() {
-            self.start = self.current;
-            self.scan_token();
+            line: 1,
+            column: 1,
+            start_line: 1,
+            start_column: 1,
+            lossless: false,
+            trivia: vec![],
+            finished: false,
         }
-
-        self.tokens.push(Token {
-            token_type: Eof,
-            lexeme: "".to_string(),
-            line: self.line,
-        });
-
-        self.tokens.clone()
     }
 
-    pub fn scan_token(&mut self) {
+    // Scans the next lexeme. Returns None when it was only whitespace or a comment.
+    fn scan_token(&mut self) -> Option<Result<Token, ScanError>> {
         let c = self.advance();
 
         match c {
@@ -397,11 +315,13 @@ impl Scanner {
             }
             '/' => {
                 if self.match_next('/') {
-                    while self.peek() != '\n' && !self.is_at_end() {
+                    // A CRLF line ending isn't part of the comment either
+                    while self.peek() != '\n' && (self.peek(), self.peek_next()) != ('\r', '\n') && !self.is_at_end() {
                         self.advance();
                     }
+                    self.add_trivia(TriviaKind::Comment)
                 } else {
-                    self.add_token(Slash);
+                    self.add_token(Slash)
                 }
             }
 
@@ -411,66 +331,54 @@ impl Scanner {
 
             'a'..='z' | 'A'..='Z' => self.identifier(),
 
-            ' ' | '\r' | '\t' => (), // Ignore whitespace
+            ' ' | '\r' | '\t' | '\n' => self.add_trivia(TriviaKind::Whitespace),
 
-            '\n' => self.line = self.line + 1,
-
-            _ => error(self.line, "Unexpected character"),
+            _ => self.error("Unexpected character"),
         }
     }
 
-    fn identifier(&mut self) {
+    fn identifier(&mut self) -> Option<Result<Token, ScanError>> {
         while self.peek().is_alphanumeric() {
             self.advance();
         }
 
-        let text = &self.source[self.start..self.current];
-        let token_type = self.keywords.get(text);
-
-        if token_type.is_some() {
-            self.add_token(token_type.unwrap().clone());
-        } else {
-            self.add_token(Identifier(text.to_string()));
+        match self.keywords.get(&self.lexeme) {
+            Some(&kind) => self.add_token(kind),
+            None => self.add_token(Identifier),
         }
     }
 
-    fn number_literal(&mut self) {
-        while self.peek().is_digit(10) {
+    fn number_literal(&mut self) -> Option<Result<Token, ScanError>> {
+        while self.peek().is_ascii_digit() {
             self.advance();
         }
 
-        if self.peek() == '.' && self.peek_next().is_digit(10) {
+        if self.peek() == '.' && self.peek_next().is_ascii_digit() {
             self.advance();
 
-            while self.peek().is_digit(10) {
+            while self.peek().is_ascii_digit() {
                 self.advance();
             }
         }
 
-        let number_text = &self.source[self.start..self.current];
-        let value: f32 = number_text.parse().expect("Failed to parse number");
-        self.add_token(Number(value));
+        let value: f32 = self.lexeme.parse().expect("Failed to parse number");
+        self.add_literal_token(Number, Literal::Number(value))
     }
 
-    fn string_literal(&mut self) {
+    fn string_literal(&mut self) -> Option<Result<Token, ScanError>> {
         while self.peek() != '\"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line = self.line + 1;
-            }
-
             self.advance();
         }
 
         if self.is_at_end() {
-            error(self.line, "Unterminated string.");
-            return;
+            return self.error("Unterminated string.");
         }
 
         // the closing "
         self.advance();
 
-        let value = &self.source[self.start + 1..self.current - 1];
-        self.add_token(StringLiteral(value.to_string()));
+        let value = &self.lexeme[1..self.lexeme.len() - 1];
+        self.add_literal_token(StringLiteral, Literal::String(value.to_string()))
     }
 
     fn match_next(&mut self, expected: char) -> bool {
@@ -482,42 +390,115 @@ impl Scanner {
         }
     }
 
-    fn peek(&mut self) -> char {
-        if self.is_at_end() {
-            return '\0';
+    // Pulls characters from the source until `count` are buffered or the source runs out
+    fn fill(&mut self, count: usize) {
+        while self.lookahead.len() < count {
+            match self.source.next() {
+                Some(c) => self.lookahead.push_back(c),
+                None => break,
+            }
         }
+    }
 
-        let c = self.source.as_str().chars().nth(self.current).unwrap();
-        c
+    fn peek(&mut self) -> char {
+        self.fill(1);
+        self.lookahead.front().copied().unwrap_or('\0')
     }
 
     fn peek_next(&mut self) -> char {
-        if self.current + 1 >= self.source.len() {
-            return '\0';
+        self.fill(2);
+        self.lookahead.get(1).copied().unwrap_or('\0')
+    }
+
+    fn advance(&mut self) -> char {
+        self.fill(1);
+        let c = self.lookahead.pop_front().unwrap_or('\0');
+        self.lexeme.push(c);
+
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
         }
 
-        let c = self.source.as_str().chars().nth(self.current + 1).unwrap();
         c
     }
 
-    fn advance(&mut self) -> char {
-        let c = self.peek();
-        self.current = self.current + 1;
-        c
+    fn is_at_end(&mut self) -> bool {
+        self.fill(1);
+        self.lookahead.is_empty()
     }
 
-    fn is_at_end(&self) -> bool {
-        self.current >= self.source.len()
+    fn add_token(&mut self, kind: TokenKind) -> Option<Result<Token, ScanError>> {
+        self.make_token(kind, None)
     }
 
-    fn add_token(&mut self, token_type: TokenType) {
-        let text = &self.source[self.start..self.current];
+    fn add_literal_token(&mut self, kind: TokenKind, literal: Literal) -> Option<Result<Token, ScanError>> {
+        self.make_token(kind, Some(literal))
+    }
+
+    fn make_token(&mut self, kind: TokenKind, literal: Option<Literal>) -> Option<Result<Token, ScanError>> {
         let token = Token {
-            token_type,
-            lexeme: text.to_string(),
-            line: self.line,
+            kind,
+            lexeme: std::mem::take(&mut self.lexeme),
+            literal,
+            line: self.start_line,
+            column: self.start_column,
+            leading_trivia: std::mem::take(&mut self.trivia),
         };
 
-        self.tokens.push(token);
+        Some(Ok(token))
+    }
+
+    fn add_trivia(&mut self, kind: TriviaKind) -> Option<Result<Token, ScanError>> {
+        let text = std::mem::take(&mut self.lexeme);
+
+        if !self.lossless {
+            return None;
+        }
+
+        // Runs of whitespace are kept as a single piece of trivia
+        if let Some(last) = self.trivia.last_mut() {
+            if last.kind == kind && kind == TriviaKind::Whitespace {
+                last.text.push_str(&text);
+                return None;
+            }
+        }
+
+        self.trivia.push(Trivia { kind, text });
+        None
+    }
+
+    fn error(&mut self, message: &str) -> Option<Result<Token, ScanError>> {
+        self.add_trivia(TriviaKind::Unknown);
+
+        Some(Err(ScanError {
+            line: self.start_line,
+            column: self.start_column,
+            message: message.to_string(),
+        }))
+    }
+}
+
+impl<I: Iterator<Item = char>> Iterator for Scanner<I> {
+    type Item = Result<Token, ScanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.finished {
+            self.start_line = self.line;
+            self.start_column = self.column;
+
+            if self.is_at_end() {
+                self.finished = true;
+                return self.add_token(Eof);
+            }
+
+            if let Some(result) = self.scan_token() {
+                return Some(result);
+            }
+        }
+
+        None
     }
 }
@@ -0,0 +1,200 @@
+use crate::expr::Expr;
+use crate::scanner::{Span, Token};
+use std::rc::Rc;
+
+// Statements mirror expressions: a closed set of node types walked by a StatementProcessor.
+// There is no for statement; the parser desugars for loops into while loops, each node of which
+// has the span of the whole for statement.
+#[derive(Clone, Debug)]
+pub enum Stmt {
+    Block(BlockStmt),
+    Class(ClassStmt),
+    Expression(ExpressionStmt),
+    Function(Rc<FunctionStmt>),
+    If(IfStmt),
+    Print(PrintStmt),
+    Return(ReturnStmt),
+    Throw(ThrowStmt),
+    Try(TryStmt),
+    Var(VarStmt),
+    While(WhileStmt),
+}
+
+impl Stmt {
+    pub fn attach<R>(&self, statement_processor: &mut dyn StatementProcessor<R>) -> R {
+        match self {
+            Stmt::Block(block_stmt) => statement_processor.process_block_stmt(block_stmt),
+            Stmt::Class(class_stmt) => statement_processor.process_class_stmt(class_stmt),
+            Stmt::Expression(expression_stmt) => statement_processor.process_expression_stmt(expression_stmt),
+            Stmt::Function(function_stmt) => statement_processor.process_function_stmt(function_stmt),
+            Stmt::If(if_stmt) => statement_processor.process_if_stmt(if_stmt),
+            Stmt::Print(print_stmt) => statement_processor.process_print_stmt(print_stmt),
+            Stmt::Return(return_stmt) => statement_processor.process_return_stmt(return_stmt),
+            Stmt::Throw(throw_stmt) => statement_processor.process_throw_stmt(throw_stmt),
+            Stmt::Try(try_stmt) => statement_processor.process_try_stmt(try_stmt),
+            Stmt::Var(var_stmt) => statement_processor.process_var_stmt(var_stmt),
+            Stmt::While(while_stmt) => statement_processor.process_while_stmt(while_stmt),
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Stmt::Block(block_stmt) => block_stmt.span,
+            Stmt::Class(class_stmt) => class_stmt.span,
+            Stmt::Expression(expression_stmt) => expression_stmt.span,
+            Stmt::Function(function_stmt) => function_stmt.span,
+            Stmt::If(if_stmt) => if_stmt.span,
+            Stmt::Print(print_stmt) => print_stmt.span,
+            Stmt::Return(return_stmt) => return_stmt.span,
+            Stmt::Throw(throw_stmt) => throw_stmt.span,
+            Stmt::Try(try_stmt) => try_stmt.span,
+            Stmt::Var(var_stmt) => var_stmt.span,
+            Stmt::While(while_stmt) => while_stmt.span,
+        }
+    }
+
+    // The line of a token in the statement, for reporting
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            Stmt::Block(block_stmt) => block_stmt.statements.iter().find_map(|statement| statement.line()),
+            Stmt::Class(class_stmt) => Some(class_stmt.name.line()),
+            Stmt::Expression(expression_stmt) => expression_stmt.expression.line(),
+            Stmt::Function(function_stmt) => Some(function_stmt.name.line()),
+            Stmt::If(if_stmt) => Some(if_stmt.keyword.line()),
+            Stmt::Print(print_stmt) => print_stmt.expression.line(),
+            Stmt::Return(return_stmt) => Some(return_stmt.keyword.line()),
+            Stmt::Throw(throw_stmt) => Some(throw_stmt.keyword.line()),
+            Stmt::Try(try_stmt) => try_stmt.body.iter().find_map(|statement| statement.line()),
+            Stmt::Var(var_stmt) => Some(var_stmt.name.line()),
+            Stmt::While(while_stmt) => Some(while_stmt.keyword.line()),
+        }
+    }
+}
+
+// Block Statements -----------------------------------------------------------------------------
+
+#[derive(Clone, Debug)]
+pub struct BlockStmt {
+    pub statements: Vec<Stmt>,
+    pub span: Span,
+}
+
+// Class Statements -----------------------------------------------------------------------------
+
+#[derive(Clone, Debug)]
+pub struct ClassStmt {
+    pub name: Token,
+    // Always a variable expression when present
+    pub superclass: Option<Expr>,
+    pub methods: Vec<Rc<FunctionStmt>>,
+    pub span: Span,
+}
+
+// Expression Statements ------------------------------------------------------------------------
+
+#[derive(Clone, Debug)]
+pub struct ExpressionStmt {
+    pub expression: Expr,
+    pub span: Span,
+}
+
+// Function Statements --------------------------------------------------------------------------
+
+// Shared with the runtime function objects created from it, hence the Rc in Stmt::Function
+#[derive(Clone, Debug)]
+pub struct FunctionStmt {
+    pub name: Token,
+    pub params: Vec<Token>,
+    pub body: Vec<Stmt>,
+    pub span: Span,
+}
+
+// If Statements --------------------------------------------------------------------------------
+
+#[derive(Clone, Debug)]
+pub struct IfStmt {
+    pub keyword: Token,
+    pub condition: Expr,
+    pub then_branch: Box<Stmt>,
+    pub else_branch: Option<Box<Stmt>>,
+    pub span: Span,
+}
+
+// Print Statements -----------------------------------------------------------------------------
+
+#[derive(Clone, Debug)]
+pub struct PrintStmt {
+    pub expression: Expr,
+    pub span: Span,
+}
+
+// Return Statements ----------------------------------------------------------------------------
+
+#[derive(Clone, Debug)]
+pub struct ReturnStmt {
+    pub keyword: Token,
+    pub value: Option<Expr>,
+    pub span: Span,
+}
+
+// Throw Statements -----------------------------------------------------------------------------
+
+#[derive(Clone, Debug)]
+pub struct ThrowStmt {
+    pub keyword: Token,
+    pub value: Expr,
+    pub span: Span,
+}
+
+// Try Statements -------------------------------------------------------------------------------
+
+// The parser requires a catch clause, a finally block or both
+#[derive(Clone, Debug)]
+pub struct TryStmt {
+    pub body: Vec<Stmt>,
+    pub catch: Option<CatchClause>,
+    pub finally: Option<Vec<Stmt>>,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug)]
+pub struct CatchClause {
+    pub name: Token,
+    pub body: Vec<Stmt>,
+}
+
+// Var Statements -------------------------------------------------------------------------------
+
+#[derive(Clone, Debug)]
+pub struct VarStmt {
+    pub name: Token,
+    pub initializer: Option<Expr>,
+    pub span: Span,
+}
+
+// While Statements -----------------------------------------------------------------------------
+
+// The keyword is `for` when the loop was desugared from a for statement
+#[derive(Clone, Debug)]
+pub struct WhileStmt {
+    pub keyword: Token,
+    pub condition: Expr,
+    pub body: Box<Stmt>,
+    pub span: Span,
+}
+
+// ----------------------------------------------------------------------------------------------
+
+pub trait StatementProcessor<R> {
+    fn process_block_stmt(&mut self, block_stmt: &BlockStmt) -> R;
+    fn process_class_stmt(&mut self, class_stmt: &ClassStmt) -> R;
+    fn process_expression_stmt(&mut self, expression_stmt: &ExpressionStmt) -> R;
+    fn process_function_stmt(&mut self, function_stmt: &Rc<FunctionStmt>) -> R;
+    fn process_if_stmt(&mut self, if_stmt: &IfStmt) -> R;
+    fn process_print_stmt(&mut self, print_stmt: &PrintStmt) -> R;
+    fn process_return_stmt(&mut self, return_stmt: &ReturnStmt) -> R;
+    fn process_throw_stmt(&mut self, throw_stmt: &ThrowStmt) -> R;
+    fn process_try_stmt(&mut self, try_stmt: &TryStmt) -> R;
+    fn process_var_stmt(&mut self, var_stmt: &VarStmt) -> R;
+    fn process_while_stmt(&mut self, while_stmt: &WhileStmt) -> R;
+}
@@ -0,0 +1,286 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+use std::time::{Duration, Instant};
+
+fn rlox(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rlox")).args(args).output().expect("Unable to run rlox")
+}
+
+fn rlox_with_input(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Unable to run rlox");
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    child.wait_with_output().expect("Unable to run rlox")
+}
+
+// A file under the system temp directory, unique to this test process
+fn scratch_file(name: &str, contents: &[u8]) -> PathBuf {
+    let path = env::temp_dir().join(format!("rlox-{}-{}", std::process::id(), name));
+    fs::write(&path, contents).expect("Unable to write scratch file");
+    path
+}
+
+#[test]
+fn unreadable_script_is_an_io_error() {
+    let path = scratch_file("latin1.lox", b"print \"caf\xe9\";\n");
+    let output = rlox(&["run", path.to_str().unwrap()]);
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(output.status.code(), Some(74));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("did not contain valid UTF-8"));
+}
+
+#[test]
+fn step_limit_reports_the_line_of_an_empty_loop() {
+    let output = rlox(&["run", "--max-steps", "1000", "-e", "var a = 1;\n\nwhile (true) {}"]);
+
+    assert_eq!(output.status.code(), Some(70));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("[line: 3]"));
+}
+
+#[test]
+fn ast_json_runs_with_the_run_options() {
+    let script = scratch_file("loop.lox", b"while (true) {}\n");
+    let ast = rlox(&["ast", "--json", script.to_str().unwrap()]);
+    let path = scratch_file("loop.json", &ast.stdout);
+
+    let output = rlox(&["run", "--ast-json", "--max-steps", "5", path.to_str().unwrap()]);
+    fs::remove_file(&script).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(output.status.code(), Some(70));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Exceeded the limit of 5 steps."));
+}
+
+#[test]
+fn long_operator_chain_runs() {
+    let chain = |terms| format!("print {};", vec!["1"; terms].join(" + "));
+
+    let output = rlox(&["run", "-e", &chain(5_000)]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "5000\n");
+
+    let path = scratch_file("chain.lox", chain(100_000).as_bytes());
+    let output = rlox(&["check", path.to_str().unwrap()]);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn ordinary_recursion_runs() {
+    let source = "fun count(n) { if (n > 0) return count(n - 1) + 1; return 0; }\nprint count(100);\nprint count(900);";
+    let output = rlox(&["run", "-e", source]);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "100\n900\n");
+}
+
+#[test]
+fn runaway_recursion_is_a_stack_overflow() {
+    let output = rlox(&["run", "-e", "fun f() { f(); }\nf();"]);
+
+    assert_eq!(output.status.code(), Some(70));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("[line: 1] Error:  : Stack overflow."));
+}
+
+#[test]
+fn deep_expressions_in_deep_recursion_do_not_crash() {
+    // Whether this fits on the stack depends on the build. Either way it has to end normally.
+    let parens = format!("{}n{}", "(".repeat(250), ")".repeat(250));
+    let source = format!("fun f(n) {{ if (n <= 0) return 0; var x = {}; return f(n - 1); }}\nf(990);", parens);
+    let output = rlox(&["run", "-e", &source]);
+
+    match output.status.code() {
+        Some(0) => {}
+        Some(70) => assert!(String::from_utf8_lossy(&output.stderr).contains("Stack overflow.")),
+        code => panic!("rlox ended with {:?}", code),
+    }
+}
+
+#[test]
+fn max_allocation_is_a_budget_for_all_allocation() {
+    // Each instance is garbage straight away, but still counts
+    let source = "class Point {} for (var i = 0; i < 100; i = i + 1) Point();";
+    let output = rlox(&["run", "--max-allocation", "1000", "-e", source]);
+
+    assert_eq!(output.status.code(), Some(70));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Exceeded the allocation budget of 1000 bytes."));
+}
+
+#[test]
+fn errors_go_to_standard_error() {
+    let output = rlox(&["run", "-e", "print 1;\nnil();"]);
+
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("[line: 2] Error:  : Can only call functions and classes."));
+
+    let output = rlox(&["check", "-e", "print ;"]);
+    assert_eq!(output.status.code(), Some(65));
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Expect expression."));
+}
+
+#[test]
+fn empty_permission_paths_are_a_usage_error() {
+    for flag in ["--allow-read=", "--allow-write=", "--allow-read=a,,b", "--allow-write=a,"] {
+        let output = rlox(&["run", flag, "-e", "print 1;"]);
+
+        assert_eq!(output.status.code(), Some(64), "{}", flag);
+        assert!(output.stdout.is_empty());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("needs a comma separated list of paths"), "{}", flag);
+    }
+}
+
+#[test]
+fn stack_trace_names_each_frame_with_its_file_and_line() {
+    let source = "class Shape {\n  area() {\n    return nil * 2;\n  }\n}\n\nfun measure(shape) {\n  return shape.area();\n}\n\nmeasure(Shape());\n";
+    let path = scratch_file("trace.lox", source.as_bytes());
+    let output = rlox(&["run", path.to_str().unwrap()]);
+    fs::remove_file(&path).unwrap();
+
+    let file = path.display();
+    let expected = format!(
+        "[line: 3] Error:  : Operands must be numbers.\n    at area ({}:3)\n    at measure ({}:8)\n    at script ({}:11)\n",
+        file, file, file
+    );
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(String::from_utf8_lossy(&output.stderr), expected);
+}
+
+#[test]
+fn stack_trace_of_deep_recursion_is_cut_short() {
+    let output = rlox(&["run", "-e", "fun down(n) {\n  if (n == 0) nil();\n  down(n - 1);\n}\ndown(30);"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let lines: Vec<&str> = stderr.lines().collect();
+
+    // 31 calls to down and the script itself, of which the innermost 16 are shown
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(lines.len(), 18, "{}", stderr);
+    assert_eq!(lines[0], "[line: 2] Error:  : Can only call functions and classes.");
+    assert_eq!(lines[1], "    at down (<eval>:2)");
+    assert!(lines[2..17].iter().all(|line| *line == "    at down (<eval>:3)"), "{}", stderr);
+    assert_eq!(lines[17], "    ... 16 more");
+}
+
+#[test]
+fn timeout_stops_an_endless_loop() {
+    let start = Instant::now();
+    let output = rlox(&["run", "--timeout", "0.2", "-e", "print 1;\nwhile (true) {}"]);
+
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("[line: 2] Error:  : Timed out."));
+    assert!(start.elapsed() < Duration::from_secs(10));
+}
+
+#[test]
+fn natives_read_and_write_files() {
+    let dir = env::temp_dir().join(format!("rlox-{}-files", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("note.txt");
+    let allow_read = format!("--allow-read={}", dir.display());
+    let allow_write = format!("--allow-write={}", dir.display());
+
+    let source = format!(
+        "writeFile(\"{0}\", \"first\nsecond\");\n\
+         print readFile(\"{0}\");\n\
+         try {{ readFile(\"{1}\"); }} catch (e) {{ print e.message; }}\n\
+         try {{ writeFile(\"{0}\", 1); }} catch (e) {{ print e.message; }}\n\
+         try {{ readFile(nil); }} catch (e) {{ print e.message; }}\n\
+         try {{ readFile(); }} catch (e) {{ print e.message; }}",
+        file.display(),
+        dir.join("missing.txt").display()
+    );
+    let output = rlox(&["run", &allow_read, &allow_write, "-e", &source]);
+    let written = fs::read_to_string(&file);
+
+    // Without the flags, the same file is off limits
+    let source = format!(
+        "try {{ readFile(\"{0}\"); }} catch (e) {{ print e.message; }}\n\
+         try {{ writeFile(\"{0}\", \"x\"); }} catch (e) {{ print e.message; }}",
+        file.display()
+    );
+    let denied = rlox(&["run", "-e", &source]);
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(written.unwrap(), "first\nsecond");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!(
+            "first\nsecond\n\
+             Unable to read '{}': No such file or directory (os error 2).\n\
+             writeFile() expects a string, not number.\n\
+             readFile() expects a string, not nil.\n\
+             Expected 1 arguments but got 0.\n",
+            dir.join("missing.txt").display()
+        )
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&denied.stdout),
+        format!("Reading '{0}' is not permitted.\nWriting '{0}' is not permitted.\n", file.display())
+    );
+}
+
+#[test]
+fn natives_read_environment_variables() {
+    let source = "print env(\"RLOX_TEST_VALUE\");\n\
+                  print env(\"RLOX_TEST_UNSET\");\n\
+                  try { env(1); } catch (e) { print e.message; }";
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args(["run", "--allow-env", "-e", source])
+        .env("RLOX_TEST_VALUE", "some value")
+        .env_remove("RLOX_TEST_UNSET")
+        .output()
+        .expect("Unable to run rlox");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "some value\nnil\nenv() expects a string, not number.\n");
+
+    let output = rlox(&["run", "-e", "env(\"HOME\");"]);
+    assert_eq!(output.status.code(), Some(70));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Reading the environment variable 'HOME' is not permitted."));
+}
+
+#[test]
+fn natives_run_programs() {
+    let source = "print exec(\"echo hello   there\");\n\
+                  try { exec(\"\"); } catch (e) { print e.message; }\n\
+                  try { exec(\"rlox-no-such-program\"); } catch (e) { print e.message; }\n\
+                  try { exec(nil); } catch (e) { print e.message; }";
+    let output = rlox(&["run", "--allow-run", "-e", source]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "hello there\n\n\
+         exec() expects a command.\n\
+         Unable to run 'rlox-no-such-program': No such file or directory (os error 2).\n\
+         exec() expects a string, not nil.\n"
+    );
+
+    let output = rlox(&["run", "-e", "exec(\"echo hi\");"]);
+    assert_eq!(output.status.code(), Some(70));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Running 'echo' is not permitted."));
+}
+
+#[test]
+fn natives_read_standard_input_and_arguments() {
+    let source = "var line = input();\n\
+                  while (line != nil) { print \"got \" + line; line = input(); }\n\
+                  try { input(1); } catch (e) { print e.message; }\n\
+                  print len(args());\n\
+                  print at(args(), 1);";
+    let output = rlox_with_input(&["run", "-e", source, "a", "b"], "one\r\ntwo\n\nlast");
+
+    assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "got one\ngot two\ngot \ngot last\nExpected 0 arguments but got 1.\n2\nb\n"
+    );
+}
@@ -0,0 +1,188 @@
+use rlox::{HostClass, Lox, LoxError, LoxObject};
+use std::convert::TryFrom;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+// A sink that keeps what is written to it, and a handle to read that back
+fn capture() -> Rc<RefCell<Vec<u8>>> {
+    Rc::new(RefCell::new(Vec::new()))
+}
+
+fn text(sink: &Rc<RefCell<Vec<u8>>>) -> String {
+    String::from_utf8_lossy(&sink.borrow()).into_owned()
+}
+
+#[test]
+fn output_and_diagnostics_go_to_their_own_sinks() {
+    let mut lox = Lox::new();
+    let output = capture();
+    let diagnostics = capture();
+    lox.set_output(output.clone());
+    lox.set_diagnostics(diagnostics.clone());
+
+    lox.eval("print \"hello\";").unwrap();
+    assert!(matches!(lox.eval("print 1 +;"), Err(LoxError::Syntax)));
+    // Runtime errors come back to the caller rather than being reported
+    let error = lox.eval("print 2;\nundefined;").unwrap_err();
+    assert!(error.to_string().starts_with("[line 2] Undefined variable 'undefined'."));
+
+    assert_eq!(text(&output), "hello\n2\n");
+    assert_eq!(text(&diagnostics), "[line: 1] Error: at ';' : Expect expression.\n");
+}
+
+#[test]
+fn nesting_deeper_than_the_stack_is_an_error() {
+    // Test threads have the 2MB stack the interpreter assumes by default
+    let mut lox = Lox::new();
+    lox.set_max_call_depth(1_000_000);
+    lox.eval("fun count(n) { if (n > 0) return count(n - 1) + 1; return 0; }").unwrap();
+
+    let error = lox.eval("count(100000)").unwrap_err();
+    assert!(error.to_string().starts_with("[line 1] Stack overflow."));
+
+    let call = format!("{}deep(n - 1){}", "(".repeat(60), ")".repeat(60));
+    let error = lox.eval(&format!("fun deep(n) {{ if (n <= 0) return 0; return {}; }} deep(1000)", call)).unwrap_err();
+    assert!(error.to_string().starts_with("[line 1] Stack overflow."));
+
+    let parens = format!("{}1{}", "(".repeat(200), ")".repeat(200));
+    assert!(matches!(lox.eval(&parens), Err(LoxError::Syntax)));
+}
+
+#[test]
+fn call_depth_is_configurable() {
+    let mut lox = Lox::new();
+    lox.eval("fun count(n) { if (n > 0) return count(n - 1) + 1; return 0; }").unwrap();
+    lox.set_max_call_depth(10);
+
+    assert_eq!(lox.eval("count(8)").unwrap().to_string(), "8");
+    assert!(lox.eval("count(20)").unwrap_err().to_string().starts_with("[line 1] Stack overflow."));
+}
+
+#[test]
+fn another_thread_can_interrupt_a_script() {
+    let mut lox = Lox::new();
+    let interrupt = lox.interrupt_handle();
+    let done = Arc::new(AtomicBool::new(false));
+
+    // eval clears an interrupt left over from before it started, so keep sending them until the
+    // loop has been stopped
+    let stopper = {
+        let done = done.clone();
+        thread::spawn(move || {
+            while !done.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(10));
+                interrupt.interrupt();
+            }
+        })
+    };
+
+    let result = lox.eval("var i = 0;\nwhile (true) { i = i + 1; }");
+    done.store(true, Ordering::Relaxed);
+    stopper.join().unwrap();
+
+    match result {
+        Err(LoxError::Interrupted(error)) => assert_eq!(error.message(), "Interrupted."),
+        other => panic!("expected an interrupt, got {:?}", other),
+    }
+    // What ran before the interrupt stays done, and the interpreter can carry on
+    assert!(lox.eval("i").unwrap().to_string().parse::<f32>().unwrap() > 0.0);
+    assert_eq!(lox.eval("1 + 1").unwrap().to_string(), "2");
+}
+
+struct Account {
+    owner: String,
+    balance: f32,
+}
+
+fn account_class() -> HostClass<Account> {
+    HostClass::new("Account", 1, |arguments| {
+        Ok(Account { owner: String::try_from(&arguments[0])?, balance: 0.0 })
+    })
+    .method("deposit", 1, |account, arguments| {
+        let amount = f32::try_from(&arguments[0])?;
+        account.borrow_mut().balance += amount;
+        Ok(LoxObject::from(account.borrow().balance))
+    })
+    .method("withdraw", 1, |account, arguments| {
+        let amount = f32::try_from(&arguments[0])?;
+        if amount > account.borrow().balance {
+            return Err("Insufficient funds.".to_string());
+        }
+        account.borrow_mut().balance -= amount;
+        Ok(LoxObject::from(account.borrow().balance))
+    })
+    .getter("balance", |account| LoxObject::from(account.borrow().balance))
+    .getter("owner", |account| LoxObject::from(account.borrow().owner.as_str()))
+    .setter("owner", |account, value| {
+        account.borrow_mut().owner = String::try_from(value)?;
+        Ok(())
+    })
+}
+
+#[test]
+fn host_classes_can_be_used_and_subclassed_from_lox() {
+    let mut lox = Lox::new();
+    let output = capture();
+    lox.set_output(output.clone());
+    lox.register_class(account_class());
+
+    lox.eval("var a = Account(\"ada\"); a.deposit(50); print a.withdraw(20); print a.balance;").unwrap();
+    lox.eval("a.owner = \"grace\"; print a.owner; print a; print Account; print type(a);").unwrap();
+    assert_eq!(lox.eval("a.balance").unwrap(), LoxObject::Number(30.0));
+
+    // A Lox subclass can call the host initializer and methods through super and this, and
+    // override host methods
+    lox.eval(
+        "class Savings < Account {
+           init(owner, rate) {
+             super.init(owner);
+             this.rate = rate;
+           }
+           addInterest() { return this.deposit(this.balance * this.rate); }
+           withdraw(amount) { print \"No withdrawals from \" + this.owner + \".\"; }
+         }
+         var s = Savings(\"lin\", 0.5);
+         s.deposit(100);
+         print s.addInterest();
+         s.withdraw(10);
+         print s.balance;
+         print s;",
+    )
+    .unwrap();
+
+    assert_eq!(
+        text(&output),
+        "30\n30\ngrace\nAccount instance\nAccount\ninstance\n150\nNo withdrawals from lin.\n150\nSavings instance\n"
+    );
+}
+
+#[test]
+fn host_class_errors_are_runtime_errors() {
+    let mut lox = Lox::new();
+    lox.register_class(account_class());
+    lox.eval("var a = Account(\"ada\"); a.deposit(10);").unwrap();
+
+    let cases = [
+        ("a.withdraw(1000);", "Insufficient funds."),
+        ("Account(1);", "Expected a string but got a number."),
+        ("Account();", "Expected 1 arguments but got 0."),
+        ("a.deposit(\"x\");", "Expected a number but got a string."),
+        ("a.deposit(1, 2);", "Expected 1 arguments but got 2."),
+        ("a.balance = 5;", "Property 'balance' is read-only."),
+        ("a.owner = 3;", "Expected a string but got a number."),
+        ("a.missing;", "Undefined property 'missing'."),
+    ];
+
+    for (code, message) in cases {
+        match lox.eval(code) {
+            Err(LoxError::Runtime(error)) => assert_eq!(error.message(), message, "{}", code),
+            other => panic!("{} gave {:?}", code, other),
+        }
+    }
+    // A failed call leaves the instance as it was
+    assert_eq!(lox.eval("a.balance").unwrap(), LoxObject::Number(10.0));
+}
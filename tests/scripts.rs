@@ -0,0 +1,14 @@
+use std::process::Command;
+
+// Runs the Lox scripts under tests/scripts through `rlox test`, which checks their output
+// against the `// expect: ` comments in them
+#[test]
+fn scripts() {
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .arg("test")
+        .arg(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/scripts"))
+        .output()
+        .expect("Unable to run rlox");
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stdout));
+}